@@ -2,30 +2,101 @@ use std::fmt;
 use std::path::PathBuf;
 
 use iced::alignment::{Alignment, Horizontal};
-use iced::widget::checkbox;
+use iced::widget::{checkbox, pick_list, text_editor};
 use iced::{
     widget::{button, center, column, container, row, text, text_input, Row},
     Element, Subscription, Task,
 };
 use rfd::FileDialog;
 
-use crate::{error::Error, zip::Progress};
+use crate::{
+    backend::CancelToken,
+    error::Error,
+    zip::{
+        collect_compressed_files, default_concurrency, format_unix_time, list_dir, parse_date,
+        read_password_file, ArchiveEntry, ArchiveListing, ConflictPolicy, DetectPolicy,
+        EntryFilter, ExtractOutcome, Layout, Limits, Progress, ScanPolicy, UnzipOptions, Usage,
+    },
+};
 
+mod session;
+mod subscription;
 mod zipfiles;
 
+use session::Session;
 use zipfiles::{ZipFiles, ZipsHandleState};
 
+const MEGABYTE: u64 = 1024 * 1024;
+
 #[derive(Clone, Debug)]
 pub enum Message {
     InputPathChange(String),
     OutputPathChange(String),
     InputPathFileDialog,
     OutputPathFileDialog,
-    PasswordChange(String),
+    PasswordsEdit(text_editor::Action),
+    PasswordFileDialog,
     Start,
+    List,
+    Listed(Result<Vec<ArchiveListing>, Error>),
+    Cancel,
+    Pause,
+    Resume,
+    DiscardSession,
+    LayerCancel(usize),
+    KeepPartialToggled(bool),
+    IgnoreDiskSpaceToggled(bool),
     ZipFileHandleProgress((usize, Result<Progress, Error>)),
     Next,
     AutoRunCheckboxToggled(bool),
+    ConcurrencyChange(String),
+    DetectPolicySelected(DetectPolicy),
+    LayoutSelected(Layout),
+    ConflictPolicySelected(ConflictPolicy),
+    LimitChange((LimitField, String)),
+    DeleteArchivesToggled(bool),
+    IncludePatternsChange(String),
+    ExcludePatternsChange(String),
+    ScanChange((ScanField, String)),
+    FollowSymlinksToggled(bool),
+    ArchivePasswordChange((usize, usize, String)),
+    ArchivePasswordSubmit((usize, usize)),
+    ArchivePasswordSkip((usize, usize)),
+    ArchivePasswordRetried((usize, usize, Result<ExtractOutcome, Error>)),
+    ArchivePasswordRevealToggled((usize, usize)),
+    ArchivePreviewToggled((usize, usize)),
+    ArchivePreviewLoaded((usize, usize, Result<Vec<ArchiveEntry>, Error>)),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LimitField {
+    Depth,
+    // 界面上以 MB 为单位
+    TotalMegabytes,
+    Ratio,
+    Files,
+}
+
+// 界面上以 MB 为单位输入字节数, 清空表示不限制, 无法解析或换算成字节后溢出时保留原来的值
+fn megabytes(s: &str, current: Option<u64>) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+
+    s.parse::<u64>()
+        .ok()
+        .and_then(|mb| mb.checked_mul(MEGABYTE))
+        .or(current)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScanField {
+    ExcludeDirs,
+    // 界面上以 MB 为单位
+    MinMegabytes,
+    MaxMegabytes,
+    ModifiedAfter,
+    ModifiedBefore,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -33,6 +104,8 @@ enum State {
     NeedInit,
     Running,
     Finish,
+    Cancelled,
+    Paused,
 }
 
 impl fmt::Display for State {
@@ -41,6 +114,8 @@ impl fmt::Display for State {
             State::NeedInit => write!(f, "需要初始化"),
             State::Running => write!(f, "正在解压"),
             State::Finish => write!(f, "解压完成"),
+            State::Cancelled => write!(f, "已取消"),
+            State::Paused => write!(f, "已暂停"),
         }
     }
 }
@@ -48,41 +123,255 @@ impl fmt::Display for State {
 pub struct ZipDive {
     input_path: PathBuf,
     output_path: PathBuf,
-    password: String,
+    passwords: text_editor::Content,
     zip_files: Vec<ZipFiles>,
     now_run_zip_files: usize,
     auto_run: bool,
+    concurrency: usize,
+    keep_partial: bool,
+    ignore_disk_space: bool,
+    detect_policy: DetectPolicy,
+    layout: Layout,
+    conflict: ConflictPolicy,
+    delete_archives: bool,
+    // 空格或逗号分隔的 glob 模式, 开始解压时再解析
+    include_patterns: String,
+    exclude_patterns: String,
+    // 大小和是否跟随符号链接直接保存, 目录和日期保存输入的原文
+    scan: ScanPolicy,
+    exclude_dirs_input: String,
+    modified_after_input: String,
+    modified_before_input: String,
+    limits: Limits,
+    usage: Usage,
     state: State,
+    // 按下暂停时正在解压的层可能直接完成, 完成后不再进入下一层
+    pause_requested: bool,
+    // 上次没有完成的会话, 可以继续解压
+    resumable: Option<Session>,
+    // 会话保存的位置, 找不到数据目录时为 None, 不保存会话
+    session_path: Option<PathBuf>,
 }
 
 impl ZipDive {
     pub fn new() -> (Self, Task<Message>) {
         (
-            Self {
-                input_path: PathBuf::from("/home/lizqwer/TempProject/zipdive/source"),
-                output_path: PathBuf::from("/home/lizqwer/TempProject/zipdive/output"),
-                password: String::from(""),
-                zip_files: Vec::new(),
-                now_run_zip_files: 0,
-                auto_run: false,
-                state: State::NeedInit,
-            },
+            Self::with_session_path(Session::default_path()),
             Task::none(),
         )
     }
 
+    fn with_session_path(session_path: Option<PathBuf>) -> Self {
+        Self {
+            input_path: PathBuf::from("/home/lizqwer/TempProject/zipdive/source"),
+            output_path: PathBuf::from("/home/lizqwer/TempProject/zipdive/output"),
+            passwords: text_editor::Content::new(),
+            zip_files: Vec::new(),
+            now_run_zip_files: 0,
+            auto_run: false,
+            concurrency: default_concurrency(),
+            keep_partial: false,
+            ignore_disk_space: false,
+            detect_policy: DetectPolicy::default(),
+            layout: Layout::default(),
+            conflict: ConflictPolicy::default(),
+            delete_archives: false,
+            include_patterns: String::new(),
+            exclude_patterns: String::new(),
+            scan: ScanPolicy::default(),
+            exclude_dirs_input: String::new(),
+            modified_after_input: String::new(),
+            modified_before_input: String::new(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            state: State::NeedInit,
+            pause_requested: false,
+            resumable: session_path.as_deref().and_then(Session::load),
+            session_path,
+        }
+    }
+
+    fn load_session(&self) -> Option<Session> {
+        self.session_path.as_deref().and_then(Session::load)
+    }
+
+    fn remove_session(&self) {
+        if let Some(path) = &self.session_path {
+            Session::remove(path);
+        }
+    }
+
+    fn unzip_options(&self) -> UnzipOptions {
+        UnzipOptions {
+            passwords: self
+                .passwords
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|password| !password.is_empty())
+                .collect(),
+            detect_policy: self.detect_policy,
+            concurrency: Some(self.concurrency),
+            // 每一层单独取消
+            cancel: CancelToken::default(),
+            keep_partial: self.keep_partial,
+            limits: self.limits,
+            usage: self.usage.clone(),
+            ignore_disk_space: self.ignore_disk_space,
+            filter: self.entry_filter(),
+            scan: self.scan_policy().unwrap_or_else(|_| self.scan.clone()),
+            conflict: self.conflict,
+            ..Default::default()
+        }
+    }
+
+    // 空的日期表示不限制, 无法解析时返回提示
+    fn scan_policy(&self) -> Result<ScanPolicy, String> {
+        let date = |s: &str| {
+            let s = s.trim();
+            if s.is_empty() {
+                return Ok(None);
+            }
+            parse_date(s)
+                .map(Some)
+                .ok_or_else(|| format!("日期格式应为 YYYY-MM-DD: {}", s))
+        };
+
+        let scan = ScanPolicy {
+            exclude_dirs: EntryFilter::parse_patterns(&self.exclude_dirs_input),
+            modified_after: date(&self.modified_after_input)?,
+            modified_before: date(&self.modified_before_input)?,
+            ..self.scan.clone()
+        };
+        scan.matcher().map_err(|e| e.to_string())?;
+
+        Ok(scan)
+    }
+
+    fn entry_filter(&self) -> EntryFilter {
+        EntryFilter {
+            include: EntryFilter::parse_patterns(&self.include_patterns),
+            exclude: EntryFilter::parse_patterns(&self.exclude_patterns),
+        }
+    }
+
+    fn layer_options(&self, depth: usize) -> UnzipOptions {
+        let mut options = self.unzip_options();
+        // 不删除用户的原始压缩包
+        options.delete_archives =
+            self.layout == Layout::Nested && self.delete_archives && depth > 1;
+        // 第一层之后只处理上一层解压出来的文件
+        if depth > 1 {
+            options.candidates = self
+                .zip_files
+                .get(depth - 2)
+                .map(|zip_files| zip_files.produced_files().collect());
+        }
+
+        options
+    }
+
+    fn push_layer(&mut self, depth: usize) {
+        let (input_path, output_path) =
+            self.layout
+                .layer_paths(&self.input_path, &self.output_path, depth);
+        let options = self.layer_options(depth);
+        let mut zip_files = ZipFiles::new(input_path, output_path, depth, options.clone());
+
+        // 超出最大层数时, 只有上一层还解压出了压缩包才算超出限制, 第一层直接算超出
+        if let Err(e) = self.limits.check_depth(depth) {
+            let candidates = options.candidates.as_deref().unwrap_or_default();
+            if depth == 1 || !collect_compressed_files(candidates, options.detect_policy).is_empty()
+            {
+                zip_files.limit_exceeded(e);
+            }
+        }
+
+        self.zip_files.push(zip_files);
+        self.layer_updated(depth);
+    }
+
+    fn session(&self) -> Session {
+        Session {
+            input_path: self.input_path.clone(),
+            output_path: self.output_path.clone(),
+            passwords: self.unzip_options().passwords,
+            auto_run: self.auto_run,
+            concurrency: self.concurrency,
+            keep_partial: self.keep_partial,
+            ignore_disk_space: self.ignore_disk_space,
+            detect_policy: self.detect_policy,
+            layout: self.layout,
+            delete_archives: self.delete_archives,
+            filter: self.entry_filter(),
+            scan: self.unzip_options().scan,
+            conflict: self.conflict,
+            limits: self.limits,
+            used_bytes: self.usage.bytes(),
+            used_files: self.usage.files(),
+            layers: self.zip_files.iter().map(ZipFiles::to_session).collect(),
+        }
+    }
+
+    fn save_session(&mut self) {
+        let session = self.session();
+        let saved = self
+            .session_path
+            .as_deref()
+            .ok_or(Error::SystemNotSupport)
+            .and_then(|path| session.save(path));
+        if let Err(e) = saved {
+            println!("保存会话失败: {}", e);
+        }
+        if self.state == State::Paused {
+            self.resumable = Some(session);
+        }
+    }
+
+    fn restore_session(&mut self, session: Session) {
+        self.input_path = session.input_path;
+        self.output_path = session.output_path;
+        self.passwords = text_editor::Content::with_text(&session.passwords.join("\n"));
+        self.auto_run = session.auto_run;
+        self.concurrency = session.concurrency;
+        self.keep_partial = session.keep_partial;
+        self.ignore_disk_space = session.ignore_disk_space;
+        self.detect_policy = session.detect_policy;
+        self.layout = session.layout;
+        self.conflict = session.conflict;
+        self.delete_archives = session.delete_archives;
+        self.include_patterns = session.filter.include.join(" ");
+        self.exclude_patterns = session.filter.exclude.join(" ");
+        let date = |secs: Option<u64>| {
+            secs.map(|secs| format_unix_time(secs)[..10].to_string())
+                .unwrap_or_default()
+        };
+        self.exclude_dirs_input = session.scan.exclude_dirs.join(" ");
+        self.modified_after_input = date(session.scan.modified_after);
+        self.modified_before_input = date(session.scan.modified_before);
+        self.scan = session.scan;
+        self.limits = session.limits;
+        self.usage = Usage::new(session.used_bytes, session.used_files);
+
+        self.zip_files.clear();
+        for layer in session.layers {
+            let options = self.layer_options(layer.depth);
+            self.zip_files.push(ZipFiles::from_session(layer, options));
+        }
+        self.now_run_zip_files = self.zip_files.len();
+        self.state = State::Running;
+        self.pause_requested = false;
+
+        if self.zip_files.is_empty() {
+            self.now_run_zip_files = 1;
+            self.push_layer(self.now_run_zip_files);
+        } else {
+            self.layer_updated(self.now_run_zip_files);
+        }
+    }
+
     fn next_zip_files(&mut self) {
         self.now_run_zip_files += 1;
-
-        let input_path = self
-            .output_path
-            .join(format!("{}", self.now_run_zip_files - 1));
-        let output_path = self.output_path.join(format!("{}", self.now_run_zip_files));
-        self.zip_files.push(ZipFiles::new(
-            input_path,
-            output_path,
-            self.now_run_zip_files,
-        ));
+        self.push_layer(self.now_run_zip_files);
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
@@ -111,8 +400,25 @@ impl ZipDive {
                 }
                 Task::none()
             }
-            Message::PasswordChange(password) => {
-                self.password = password;
+            Message::PasswordsEdit(action) => {
+                self.passwords.perform(action);
+                Task::none()
+            }
+            Message::PasswordFileDialog => {
+                let file = FileDialog::new().add_filter("text", &["txt"]).pick_file();
+                if let Some(file) = file {
+                    match read_password_file(&file) {
+                        Ok(passwords) => {
+                            let mut text = self.passwords.text();
+                            if !text.trim().is_empty() && !text.ends_with('\n') {
+                                text.push('\n');
+                            }
+                            text.push_str(&passwords.join("\n"));
+                            self.passwords = text_editor::Content::with_text(&text);
+                        }
+                        Err(e) => println!("读取密码文件失败: {}", e),
+                    }
+                }
                 Task::none()
             }
             Message::Start => {
@@ -126,23 +432,121 @@ impl ZipDive {
                             println!("路径不存在");
                             return Task::none();
                         }
+                        if let Err(e) = self.entry_filter().matcher() {
+                            println!("过滤条件有误: {}", e);
+                            return Task::none();
+                        }
+                        if let Err(e) = self.scan_policy() {
+                            println!("查找条件有误: {}", e);
+                            return Task::none();
+                        }
 
                         self.zip_files.clear();
+                        self.resumable = None;
+                        self.usage = Usage::default();
                         self.now_run_zip_files = 1;
                         self.state = State::Running;
+                        self.pause_requested = false;
 
-                        // 创建第一层的输出目录
-                        let output_path = self.output_path.join("1");
-                        self.zip_files.push(ZipFiles::new(
-                            self.input_path.clone(),
-                            output_path,
-                            self.now_run_zip_files,
-                        ));
+                        self.push_layer(self.now_run_zip_files);
                     }
                 }
 
                 Task::none()
             }
+            Message::List => {
+                if self.state == State::Running {
+                    println!("正在解压, 无法预览");
+                    return Task::none();
+                }
+                let scan = match self.scan_policy() {
+                    Ok(scan) => scan,
+                    Err(e) => {
+                        println!("查找条件有误: {}", e);
+                        return Task::none();
+                    }
+                };
+
+                Task::perform(
+                    list_dir(self.input_path.clone(), self.detect_policy, scan),
+                    Message::Listed,
+                )
+            }
+            Message::Listed(listings) => {
+                // 列出内容的过程中已经开始解压了
+                if self.state == State::Running {
+                    return Task::none();
+                }
+                match listings {
+                    Ok(listings) => {
+                        let (input_path, output_path) =
+                            self.layout
+                                .layer_paths(&self.input_path, &self.output_path, 1);
+                        self.zip_files = vec![ZipFiles::listed(
+                            input_path,
+                            output_path,
+                            self.layer_options(1),
+                            listings,
+                        )];
+                    }
+                    Err(e) => println!("列出压缩包内容失败: {}", e),
+                }
+                Task::none()
+            }
+            Message::Cancel => {
+                if self.state == State::Running {
+                    self.zip_files.iter_mut().for_each(ZipFiles::cancel);
+                    self.state = State::Cancelled;
+                }
+                Task::none()
+            }
+            Message::Pause => {
+                if self.state == State::Running {
+                    self.zip_files.iter_mut().for_each(ZipFiles::pause);
+                    self.pause_requested = true;
+                    if self.zip_files.iter().any(ZipFiles::is_running) {
+                        // 等正在解压的压缩包完成后再进入暂停状态
+                        self.layer_updated(self.now_run_zip_files);
+                    } else {
+                        self.state = State::Paused;
+                        self.save_session();
+                    }
+                }
+                Task::none()
+            }
+            Message::Resume => {
+                if self.state != State::Running {
+                    match self.resumable.take().or_else(|| self.load_session()) {
+                        Some(session) => self.restore_session(session),
+                        None => println!("没有可以继续的会话"),
+                    }
+                }
+                Task::none()
+            }
+            Message::DiscardSession => {
+                self.resumable = None;
+                self.remove_session();
+                Task::none()
+            }
+            Message::LayerCancel(id) => {
+                if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
+                    zip_file.cancel();
+                    self.layer_updated(id);
+                }
+                Task::none()
+            }
+            Message::KeepPartialToggled(keep_partial) => {
+                self.keep_partial = keep_partial;
+                Task::none()
+            }
+            Message::IgnoreDiskSpaceToggled(ignore_disk_space) => {
+                self.ignore_disk_space = ignore_disk_space;
+                // 空间不足暂停后勾选忽略, 继续时用的是暂停时保存的会话
+                if let Some(session) = self.resumable.as_mut() {
+                    session.ignore_disk_space = ignore_disk_space;
+                }
+                Task::none()
+            }
             Message::Next => {
                 match self.state {
                     State::Finish => {
@@ -167,7 +571,10 @@ impl ZipDive {
                             println!("处于自动运行模式，无需手动操作");
                         }
                     }
-                    State::NeedInit => {
+                    State::Paused => {
+                        println!("已暂停, 需要继续解压");
+                    }
+                    State::NeedInit | State::Cancelled => {
                         println!("需要开始解压");
                     }
                 }
@@ -177,18 +584,47 @@ impl ZipDive {
             Message::ZipFileHandleProgress((id, progress)) => {
                 if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
                     zip_file.progress(progress);
-
-                    match zip_file.state {
-                        ZipsHandleState::Finished => {
-                            if self.auto_run {
-                                self.next_zip_files();
-                            }
-                        }
-                        ZipsHandleState::EmptyZips => {
-                            self.state = State::Finish;
-                        }
-                        _ => {}
-                    }
+                    self.layer_updated(id);
+                }
+                Task::none()
+            }
+            Message::ArchivePasswordChange((id, file_id, input)) => {
+                if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
+                    zip_file.password_input(file_id, input);
+                }
+                Task::none()
+            }
+            Message::ArchivePasswordSubmit((id, file_id)) => match self.zip_files.get_mut(id - 1) {
+                Some(zip_file) => zip_file.retry_password(file_id),
+                None => Task::none(),
+            },
+            Message::ArchivePasswordSkip((id, file_id)) => {
+                if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
+                    zip_file.skip_password(file_id);
+                    self.layer_updated(id);
+                }
+                Task::none()
+            }
+            Message::ArchivePasswordRetried((id, file_id, result)) => {
+                if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
+                    zip_file.password_retried(file_id, result);
+                    self.layer_updated(id);
+                }
+                Task::none()
+            }
+            Message::ArchivePasswordRevealToggled((id, file_id)) => {
+                if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
+                    zip_file.toggle_password_reveal(file_id);
+                }
+                Task::none()
+            }
+            Message::ArchivePreviewToggled((id, file_id)) => match self.zip_files.get_mut(id - 1) {
+                Some(zip_file) => zip_file.toggle_preview(file_id),
+                None => Task::none(),
+            },
+            Message::ArchivePreviewLoaded((id, file_id, result)) => {
+                if let Some(zip_file) = self.zip_files.get_mut(id - 1) {
+                    zip_file.preview_loaded(file_id, result);
                 }
                 Task::none()
             }
@@ -196,6 +632,125 @@ impl ZipDive {
                 self.auto_run = auto_run;
                 Task::none()
             }
+            Message::ConcurrencyChange(s) => {
+                if let Ok(concurrency) = s.trim().parse::<usize>() {
+                    if concurrency > 0 {
+                        self.concurrency = concurrency;
+                    }
+                }
+                Task::none()
+            }
+            Message::DetectPolicySelected(detect_policy) => {
+                self.detect_policy = detect_policy;
+                Task::none()
+            }
+            Message::LayoutSelected(layout) => {
+                self.layout = layout;
+                Task::none()
+            }
+            Message::ConflictPolicySelected(conflict) => {
+                self.conflict = conflict;
+                Task::none()
+            }
+            Message::LimitChange((field, s)) => {
+                self.limit_changed(field, s.trim());
+                Task::none()
+            }
+            Message::IncludePatternsChange(patterns) => {
+                self.include_patterns = patterns;
+                Task::none()
+            }
+            Message::ExcludePatternsChange(patterns) => {
+                self.exclude_patterns = patterns;
+                Task::none()
+            }
+            Message::ScanChange((field, s)) => {
+                self.scan_changed(field, s);
+                Task::none()
+            }
+            Message::FollowSymlinksToggled(follow_symlinks) => {
+                self.scan.follow_symlinks = follow_symlinks;
+                Task::none()
+            }
+            Message::DeleteArchivesToggled(delete_archives) => {
+                self.delete_archives = delete_archives;
+                Task::none()
+            }
+        }
+    }
+
+    // 清空输入表示不限制, 无法解析的输入忽略
+    fn limit_changed(&mut self, field: LimitField, s: &str) {
+        fn parse<T: std::str::FromStr>(s: &str, current: Option<T>) -> Option<T> {
+            if s.is_empty() {
+                None
+            } else {
+                s.parse().ok().or(current)
+            }
+        }
+
+        match field {
+            // 0 层什么都不会解压, 当作无效的输入
+            LimitField::Depth if s.parse() == Ok(0usize) => {}
+            LimitField::Depth => self.limits.max_depth = parse(s, self.limits.max_depth),
+            LimitField::TotalMegabytes => {
+                self.limits.max_total_bytes = megabytes(s, self.limits.max_total_bytes)
+            }
+            // NaN, 无穷大和不大于 0 的压缩比没有意义
+            LimitField::Ratio if !s.is_empty() => {
+                if let Some(ratio) = s
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+                {
+                    self.limits.max_ratio = Some(ratio);
+                }
+            }
+            LimitField::Ratio => self.limits.max_ratio = None,
+            LimitField::Files => self.limits.max_files = parse(s, self.limits.max_files),
+        }
+    }
+
+    // 大小和 limit_changed 一样处理, 目录和日期在开始解压时再解析
+    fn scan_changed(&mut self, field: ScanField, s: String) {
+        match field {
+            ScanField::ExcludeDirs => self.exclude_dirs_input = s,
+            ScanField::MinMegabytes => self.scan.min_size = megabytes(s.trim(), self.scan.min_size),
+            ScanField::MaxMegabytes => self.scan.max_size = megabytes(s.trim(), self.scan.max_size),
+            ScanField::ModifiedAfter => self.modified_after_input = s,
+            ScanField::ModifiedBefore => self.modified_before_input = s,
+        }
+    }
+
+    fn layer_updated(&mut self, id: usize) {
+        let Some(zip_file) = self.zip_files.get(id - 1) else {
+            return;
+        };
+
+        match zip_file.state {
+            ZipsHandleState::Finished => {
+                // 按下暂停时这一层的压缩包都已经在解压, 这一层完成后停在暂停状态
+                if self.pause_requested {
+                    self.state = State::Paused;
+                }
+                self.save_session();
+                if self.auto_run && self.state == State::Running {
+                    self.next_zip_files();
+                }
+            }
+            ZipsHandleState::EmptyZips | ZipsHandleState::LimitExceeded(_) => {
+                self.state = State::Finish;
+                self.remove_session();
+            }
+            ZipsHandleState::Cancelled => {
+                self.state = State::Cancelled;
+                self.remove_session();
+            }
+            ZipsHandleState::Paused => {
+                self.state = State::Paused;
+                self.save_session();
+            }
+            _ => {}
         }
     }
 
@@ -203,7 +758,7 @@ impl ZipDive {
         Subscription::batch(self.zip_files.iter().map(ZipFiles::subscription))
     }
 
-    pub fn view(&self) -> Element<Message> {
+    pub fn view(&self) -> Element<'_, Message> {
         let input_path_input_helper = text_input(
             "输入要处理的文件路径...",
             self.input_path.display().to_string().as_str(),
@@ -221,7 +776,7 @@ impl ZipDive {
 
         let output_path_input_helper = text_input(
             "输入要导出的位置...",
-            &self.output_path.display().to_string().as_str(),
+            self.output_path.display().to_string().as_str(),
         );
         let input_path_button_helper = button("select");
 
@@ -234,14 +789,137 @@ impl ZipDive {
             )
         };
 
-        let password_input =
-            text_input("输入默认压缩密码...", &self.password).on_input(Message::PasswordChange);
+        let password_input = text_editor(&self.passwords)
+            .placeholder("输入候选密码, 每行一个...")
+            .height(80)
+            .on_action(Message::PasswordsEdit);
+        let password_file_button = button("import").on_press(Message::PasswordFileDialog);
 
         let start_button = button("Start").on_press(Message::Start);
+        let mut list_button = button("List");
+        if self.state != State::Running {
+            list_button = list_button.on_press(Message::List);
+        }
         let next_button = button("Next").on_press(Message::Next);
+        let mut cancel_button = button("Cancel");
+        let mut pause_button = button("Pause");
+        if self.state == State::Running {
+            cancel_button = cancel_button.on_press(Message::Cancel);
+            pause_button = pause_button.on_press(Message::Pause);
+        }
+        let ignore_disk_space_checkbox = checkbox("忽略磁盘空间检查", self.ignore_disk_space)
+            .text_shaping(text::Shaping::Advanced)
+            .on_toggle(Message::IgnoreDiskSpaceToggled);
+        let keep_partial_checkbox = checkbox("保留部分文件", self.keep_partial)
+            .text_shaping(text::Shaping::Advanced)
+            .on_toggle(Message::KeepPartialToggled);
         let auto_run_checkbox =
             checkbox("AutoRun", self.auto_run).on_toggle(Message::AutoRunCheckboxToggled);
 
+        let concurrency_input = text_input("并发数", &self.concurrency.to_string())
+            .on_input(Message::ConcurrencyChange)
+            .width(50);
+
+        let detect_policy_list = pick_list(
+            DetectPolicy::ALL,
+            Some(self.detect_policy),
+            Message::DetectPolicySelected,
+        );
+
+        let layout_list = pick_list(Layout::ALL, Some(self.layout), Message::LayoutSelected);
+        let conflict_list = pick_list(
+            ConflictPolicy::ALL,
+            Some(self.conflict),
+            Message::ConflictPolicySelected,
+        );
+        let delete_archives_checkbox = checkbox("删除中间压缩包", self.delete_archives)
+            .text_shaping(text::Shaping::Advanced)
+            .on_toggle(Message::DeleteArchivesToggled);
+
+        let mut filter_row = row![
+            text("只解压:").shaping(text::Shaping::Advanced),
+            text_input("例如 *.log *.json", &self.include_patterns)
+                .on_input(Message::IncludePatternsChange),
+            text("排除:").shaping(text::Shaping::Advanced),
+            text_input("例如 *.dmp tmp/**", &self.exclude_patterns)
+                .on_input(Message::ExcludePatternsChange),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(10);
+        if let Err(e) = self.entry_filter().matcher() {
+            filter_row = filter_row.push(text(e.to_string()).shaping(text::Shaping::Advanced));
+        }
+
+        let scan_input = |placeholder, value: &str, field| {
+            text_input(placeholder, value)
+                .on_input(move |s| Message::ScanChange((field, s)))
+                .width(100)
+        };
+        let size_text = |size: Option<u64>| size.map(|v| (v / MEGABYTE).to_string());
+        let min_size = size_text(self.scan.min_size).unwrap_or_default();
+        let max_size = size_text(self.scan.max_size).unwrap_or_default();
+        let mut scan_row = row![
+            text("跳过目录:").shaping(text::Shaping::Advanced),
+            text_input("例如 .git node_modules", &self.exclude_dirs_input)
+                .on_input(|s| Message::ScanChange((ScanField::ExcludeDirs, s))),
+            text("大小(MB):").shaping(text::Shaping::Advanced),
+            scan_input("不限制", &min_size, ScanField::MinMegabytes),
+            text("-"),
+            scan_input("不限制", &max_size, ScanField::MaxMegabytes),
+            text("修改时间:").shaping(text::Shaping::Advanced),
+            scan_input(
+                "YYYY-MM-DD",
+                &self.modified_after_input,
+                ScanField::ModifiedAfter
+            ),
+            text("-"),
+            scan_input(
+                "YYYY-MM-DD",
+                &self.modified_before_input,
+                ScanField::ModifiedBefore
+            ),
+            checkbox("跟随符号链接", self.scan.follow_symlinks)
+                .text_shaping(text::Shaping::Advanced)
+                .on_toggle(Message::FollowSymlinksToggled),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(10);
+        if let Err(e) = self.scan_policy() {
+            scan_row = scan_row.push(text(e).shaping(text::Shaping::Advanced));
+        }
+
+        let limit_input = |value: Option<String>, field| {
+            text_input("不限制", &value.unwrap_or_default())
+                .on_input(move |s| Message::LimitChange((field, s)))
+                .width(80)
+        };
+        let limits_row = row![
+            text("最大层数:").shaping(text::Shaping::Advanced),
+            limit_input(
+                self.limits.max_depth.map(|v| v.to_string()),
+                LimitField::Depth
+            ),
+            text("最大大小(MB):").shaping(text::Shaping::Advanced),
+            limit_input(
+                self.limits
+                    .max_total_bytes
+                    .map(|v| (v / MEGABYTE).to_string()),
+                LimitField::TotalMegabytes
+            ),
+            text("最大压缩比:").shaping(text::Shaping::Advanced),
+            limit_input(
+                self.limits.max_ratio.map(|v| v.to_string()),
+                LimitField::Ratio
+            ),
+            text("最大文件数:").shaping(text::Shaping::Advanced),
+            limit_input(
+                self.limits.max_files.map(|v| v.to_string()),
+                LimitField::Files
+            ),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(10);
+
         let state_show = text(format!("状态: {}", self.state)).shaping(text::Shaping::Advanced);
 
         let controls = row![
@@ -265,13 +943,44 @@ impl ZipDive {
             column![
                 row![
                     text("解压密码:").shaping(text::Shaping::Advanced),
-                    password_input.padding(10)
+                    password_input.padding(10),
+                    password_file_button
+                ]
+                .align_y(Alignment::Center)
+                .spacing(10),
+                row![
+                    text("识别方式:").shaping(text::Shaping::Advanced),
+                    detect_policy_list,
+                    keep_partial_checkbox,
+                    ignore_disk_space_checkbox
+                ]
+                .align_y(Alignment::Center)
+                .spacing(10),
+                row![
+                    text("目录结构:").shaping(text::Shaping::Advanced),
+                    layout_list,
+                    text("同名文件:").shaping(text::Shaping::Advanced),
+                    conflict_list,
+                    delete_archives_checkbox
                 ]
                 .align_y(Alignment::Center)
                 .spacing(10),
-                row![state_show, start_button, next_button, auto_run_checkbox]
-                    .align_y(Alignment::Center)
-                    .spacing(10)
+                filter_row,
+                scan_row,
+                limits_row,
+                row![
+                    state_show,
+                    start_button,
+                    list_button,
+                    next_button,
+                    pause_button,
+                    cancel_button,
+                    auto_run_checkbox,
+                    text("并发数:").shaping(text::Shaping::Advanced),
+                    concurrency_input
+                ]
+                .align_y(Alignment::Center)
+                .spacing(10)
             ]
             .align_x(Horizontal::Center)
             .spacing(10),
@@ -286,8 +995,155 @@ impl ZipDive {
                 .into()
         };
 
-        container(column![controls, show_zip_files].spacing(10))
-            .padding(10)
-            .into()
+        let mut content = column![controls].spacing(10);
+        if let Some(session) = self
+            .resumable
+            .as_ref()
+            .filter(|_| self.state != State::Running)
+        {
+            content = content.push(
+                row![
+                    text(format!(
+                        "发现未完成的解压: {} -> {} (已解压 {} 层)",
+                        session.input_path.display(),
+                        session.output_path.display(),
+                        session.layers.len()
+                    ))
+                    .shaping(text::Shaping::Advanced),
+                    button("Resume").on_press(Message::Resume),
+                    button("Discard").on_press(Message::DiscardSession)
+                ]
+                .align_y(Alignment::Center)
+                .spacing(10),
+            );
+        }
+
+        container(content.push(show_zip_files)).padding(10).into()
+    }
+}
+
+#[cfg(test)]
+mod app_test {
+    use super::*;
+    use crate::zip::Archive;
+
+    // 会话保存到临时目录, 不读写真正的会话
+    fn test_app(temp_dir: &assert_fs::TempDir) -> ZipDive {
+        ZipDive::with_session_path(Some(temp_dir.path().join("session.json")))
+    }
+
+    #[test]
+    fn test_pause_when_all_archives_running() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input");
+        let output_path = temp_dir.path().join("output");
+        std::fs::create_dir_all(&input_path).unwrap();
+        std::fs::create_dir_all(&output_path).unwrap();
+
+        let mut app = test_app(&temp_dir);
+        app.input_path = input_path.clone();
+        app.output_path = output_path;
+        app.auto_run = true;
+
+        let progress = |progress| Message::ZipFileHandleProgress((1, Ok(progress)));
+        let _ = app.update(Message::Start);
+        let _ = app.update(progress(Progress::Searching {
+            zip_files: vec![Archive::single(input_path.join("a.zip"))],
+        }));
+        let _ = app.update(progress(Progress::Extracting { file_id: 0 }));
+
+        // 唯一的压缩包已经在解压, 这一层会直接完成
+        let _ = app.update(Message::Pause);
+        assert_eq!(app.state, State::Running);
+        let _ = app.update(progress(Progress::Zipping {
+            file_id: 0,
+            state: Ok(ExtractOutcome::default()),
+        }));
+        let _ = app.update(progress(Progress::Finished));
+
+        assert_eq!(app.state, State::Paused);
+        assert_eq!(app.zip_files.len(), 1);
+        let session = app.resumable.as_ref().unwrap();
+        assert_eq!(session.layers.len(), 1);
+        assert!(session.layers[0].finished);
+        assert!(temp_dir.path().join("session.json").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_resume_keeps_ignore_disk_space() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut app = test_app(&temp_dir);
+        app.resumable = Some(app.session());
+
+        let _ = app.update(Message::IgnoreDiskSpaceToggled(true));
+        let session = app.resumable.take().unwrap();
+        assert!(session.ignore_disk_space);
+
+        let mut app = test_app(&temp_dir);
+        app.restore_session(session);
+        assert!(app.ignore_disk_space);
+        assert!(app.layer_options(1).ignore_disk_space);
+    }
+
+    #[test]
+    fn test_limit_input() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut app = test_app(&temp_dir);
+
+        app.limit_changed(LimitField::TotalMegabytes, "100");
+        assert_eq!(app.limits.max_total_bytes, Some(100 * MEGABYTE));
+        // 换算成字节后溢出时保留原来的值
+        app.limit_changed(LimitField::TotalMegabytes, "20000000000000");
+        assert_eq!(app.limits.max_total_bytes, Some(100 * MEGABYTE));
+
+        app.limit_changed(LimitField::Depth, "3");
+        app.limit_changed(LimitField::Depth, "0");
+        assert_eq!(app.limits.max_depth, Some(3));
+
+        app.limit_changed(LimitField::Ratio, "50");
+        for invalid in ["NaN", "inf", "-1", "0"] {
+            app.limit_changed(LimitField::Ratio, invalid);
+            assert_eq!(app.limits.max_ratio, Some(50.0), "{}", invalid);
+        }
+        app.limit_changed(LimitField::Ratio, "");
+        assert_eq!(app.limits.max_ratio, None);
+    }
+
+    #[test]
+    fn test_scan_size_input() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut app = test_app(&temp_dir);
+
+        app.scan_changed(ScanField::MinMegabytes, "1".to_string());
+        app.scan_changed(ScanField::MinMegabytes, "20000000000000".to_string());
+        assert_eq!(app.scan.min_size, Some(MEGABYTE));
+        app.scan_changed(ScanField::MaxMegabytes, "20000000000000".to_string());
+        assert_eq!(app.scan.max_size, None);
+    }
+
+    #[test]
+    fn test_list_before_start() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut app = test_app(&temp_dir);
+        let listing = ArchiveListing {
+            archive: Archive::single(app.input_path.join("a.zip")),
+            entries: Ok(vec![ArchiveEntry {
+                path: PathBuf::from("a.txt"),
+                size: 1,
+                ..Default::default()
+            }]),
+        };
+
+        let _ = app.update(Message::Listed(Ok(vec![listing])));
+        assert_eq!(app.state, State::NeedInit);
+        assert_eq!(app.zip_files.len(), 1);
+        assert!(matches!(app.zip_files[0].state, ZipsHandleState::Listed));
+        assert!(!app.zip_files[0].is_running());
+
+        // 内容已经列出来了, 展开时不用再读取
+        let _ = app.update(Message::ArchivePreviewToggled((1, 0)));
+        assert!(matches!(app.zip_files[0].state, ZipsHandleState::Listed));
     }
 }
@@ -0,0 +1,117 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    zip::{Archive, ConflictPolicy, DetectPolicy, EntryFilter, Layout, Limits, ScanPolicy},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ArchiveState {
+    Pending,
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchiveSession {
+    pub archive: Archive,
+    pub state: ArchiveState,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub produced: Vec<PathBuf>,
+    #[serde(default)]
+    pub rejected: Vec<PathBuf>,
+    #[serde(default)]
+    pub filtered: usize,
+    #[serde(default)]
+    pub skipped: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayerSession {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub depth: usize,
+    // 这一层所有的压缩包都处理完了
+    pub finished: bool,
+    pub archives: Vec<ArchiveSession>,
+}
+
+// 暂停时保存的解压进度, 重启后可以从这里继续
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub passwords: Vec<String>,
+    pub auto_run: bool,
+    pub concurrency: usize,
+    pub keep_partial: bool,
+    // 因为磁盘空间不足暂停后, 勾选忽略再继续时不能又停下来
+    #[serde(default)]
+    pub ignore_disk_space: bool,
+    pub detect_policy: DetectPolicy,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub delete_archives: bool,
+    #[serde(default)]
+    pub filter: EntryFilter,
+    #[serde(default)]
+    pub scan: ScanPolicy,
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+    #[serde(default)]
+    pub limits: Limits,
+    // 已经解压出来的字节数和文件数
+    #[serde(default)]
+    pub used_bytes: u64,
+    #[serde(default)]
+    pub used_files: usize,
+    pub layers: Vec<LayerSession>,
+}
+
+impl Session {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("zipdive").join("session.json"))
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content =
+            serde_json::to_string_pretty(self).map_err(|e| Error::IoError(e.to_string()))?;
+
+        // 会话里有候选密码和用过的密码, 在 unix 上只让自己能读写
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(path)?;
+        // 之前创建的文件权限可能更宽
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(content.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn remove(path: &Path) {
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+use iced::futures::StreamExt;
+use iced::Subscription;
+
+use crate::error::Error;
+use crate::zip::{unzip_dir_s, Progress, UnzipOptions};
+
+// 把解压引擎的进度流包装成 iced 的订阅, id 为解压的层数
+pub fn run_zip_dir(
+    id: usize,
+    source_dir: PathBuf,
+    target_dir: PathBuf,
+    options: UnzipOptions,
+) -> Subscription<(usize, Result<Progress, Error>)> {
+    Subscription::run_with_id(
+        id,
+        unzip_dir_s(source_dir, target_dir, options).map(move |progress| (id, progress)),
+    )
+}
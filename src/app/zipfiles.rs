@@ -1,32 +1,69 @@
+use std::collections::HashSet;
 use std::fmt;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use iced::alignment::Alignment;
-use iced::widget::checkbox;
+use iced::widget::{button, checkbox, container, mouse_area, text_input, tooltip};
 use iced::{
     widget::{column, progress_bar, row, text, Column},
-    Element, Length, Subscription,
+    Element, Length, Subscription, Task,
 };
 
-use crate::{error::Error, zip::run_zip_dir, zip::Progress};
+use crate::{
+    error::{ArchiveErrorKind, Error},
+    zip::{
+        archive_output_dir, backend::ExtractProgress, list_archive, retry_archive, Archive,
+        ArchiveEntry, ArchiveListing, ExtractOutcome, Progress, UnzipOptions,
+    },
+};
 
+use super::session::{ArchiveSession, ArchiveState, LayerSession};
+use super::subscription::run_zip_dir;
 use super::Message;
 
 struct ZipFile {
+    archive: Archive,
+    output_dir: PathBuf,
     show_path: PathBuf,
+    volume_count: usize,
+    password: Option<String>,
+    // 默认只显示用过密码, 点击后才显示密码本身
+    password_revealed: bool,
+    error: Option<Error>,
+    // 解压出来的文件, 下一层从这些文件中查找压缩包
+    produced: Vec<PathBuf>,
+    // 会跳出输出目录而被跳过的条目
+    rejected: Vec<PathBuf>,
+    // 被 include/exclude 过滤掉的文件数
+    filtered: usize,
+    // 输出目录中已有同名文件而跳过的文件数
+    skipped: usize,
+    password_input: String,
     state: ZipFileHandleState,
+    // 开始解压的时间和最近一次的字节进度, 用来显示进度条和速度
+    started: Option<Instant>,
+    progress: ExtractProgress,
+    // 点击后展开的内容列表, 第一次展开时才读取
+    expanded: bool,
+    listing: Option<Result<Vec<ArchiveEntry>, Error>>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum ZipFileHandleState {
+    Waiting,
     Running,
     Finished,
     Error,
+    NeedPassword,
+    Cancelled,
+    // 只列出了内容, 不会解压
+    Listed,
 }
 
 impl ZipFile {
-    fn new(path: PathBuf, parent: PathBuf) -> Self {
-        let mut components = path.components();
+    fn new(archive: Archive, parent: &Path, output_path: &Path) -> Self {
+        let mut components = archive.path.components();
         let mut parent_components = parent.components();
 
         while parent_components.as_path() != Path::new("")
@@ -39,26 +76,286 @@ impl ZipFile {
         }
 
         Self {
+            output_dir: archive_output_dir(parent, &archive, output_path),
             show_path: components.as_path().to_path_buf(),
-            state: ZipFileHandleState::Running,
+            volume_count: archive.volumes.len() + archive.missing_volumes.len(),
+            archive,
+            password: None,
+            password_revealed: false,
+            error: None,
+            produced: Vec::new(),
+            rejected: Vec::new(),
+            filtered: 0,
+            skipped: 0,
+            password_input: String::new(),
+            state: ZipFileHandleState::Waiting,
+            started: None,
+            progress: ExtractProgress::default(),
+            expanded: false,
+            listing: None,
+        }
+    }
+
+    // 正在解压的压缩包已经完成的比例, 不知道总量时为 0
+    fn running_fraction(&self) -> f32 {
+        match self.state {
+            ZipFileHandleState::Running => self.progress.fraction().unwrap_or(0.0),
+            _ => 0.0,
+        }
+    }
+
+    fn throughput(&self) -> Option<String> {
+        let elapsed = self.started?.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+
+        let megabytes = self.progress.bytes as f64 / elapsed / (1024.0 * 1024.0);
+        Some(format!("{:.1} MB/s", megabytes))
+    }
+
+    fn is_limit_exceeded(&self) -> bool {
+        self.error.as_ref().is_some_and(Error::is_limit_exceeded)
+    }
+
+    fn restore(&mut self, session: &ArchiveSession) {
+        self.password = session.password.clone();
+        self.produced = session.produced.clone();
+        self.rejected = session.rejected.clone();
+        self.filtered = session.filtered;
+        self.skipped = session.skipped;
+        self.state = match session.state {
+            ArchiveState::Pending => ZipFileHandleState::Waiting,
+            ArchiveState::Done => ZipFileHandleState::Finished,
+            ArchiveState::Failed => ZipFileHandleState::Error,
+        };
+    }
+
+    fn to_session(&self) -> ArchiveSession {
+        ArchiveSession {
+            archive: self.archive.clone(),
+            state: match self.state {
+                ZipFileHandleState::Finished => ArchiveState::Done,
+                ZipFileHandleState::Error => ArchiveState::Failed,
+                _ => ArchiveState::Pending,
+            },
+            password: self.password.clone(),
+            produced: self.produced.clone(),
+            rejected: self.rejected.clone(),
+            filtered: self.filtered,
+            skipped: self.skipped,
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self, depth: usize, file_id: usize) -> Element<'_, Message> {
+        let line = self.line_view(depth, file_id);
+        if !self.expanded {
+            return line;
+        }
+
+        let preview: Element<Message> = match &self.listing {
+            None => text("读取中...").into(),
+            Some(Err(error)) => text(format!("无法列出内容: {}", error))
+                .shaping(text::Shaping::Advanced)
+                .into(),
+            Some(Ok(entries)) => entry_tree(entries),
+        };
+
+        column![line, container(preview).padding([0, 30])].into()
+    }
+
+    fn line_view(&self, depth: usize, file_id: usize) -> Element<'_, Message> {
         let start_icon: Element<Message> = match self.state {
             ZipFileHandleState::Running | ZipFileHandleState::Finished => {
                 checkbox("", self.state == ZipFileHandleState::Finished).into()
             }
+            ZipFileHandleState::Waiting => text("⏳").shaping(text::Shaping::Advanced).into(),
+            ZipFileHandleState::Error if self.is_limit_exceeded() => {
+                text("⚠").shaping(text::Shaping::Advanced).into()
+            }
             ZipFileHandleState::Error => text("❌").shaping(text::Shaping::Advanced).into(),
+            ZipFileHandleState::NeedPassword => text("🔒").shaping(text::Shaping::Advanced).into(),
+            ZipFileHandleState::Cancelled => text("⛔").shaping(text::Shaping::Advanced).into(),
+            ZipFileHandleState::Listed => text("📦").shaping(text::Shaping::Advanced).into(),
         };
 
-        row![
-            start_icon,
-            text(format!("{}", self.show_path.display(),))
-                .width(Length::Fill)
-                .shaping(text::Shaping::Advanced),
-        ]
-        .into()
+        let mut show_str = format!("{}", self.show_path.display());
+        if self.volume_count > 1 {
+            show_str.push_str(&format!(" ({} 个分卷)", self.volume_count));
+        }
+        match &self.password {
+            Some(password) if self.password_revealed => {
+                show_str.push_str(&format!(" [密码: {}]", password));
+            }
+            Some(_) => show_str.push_str(" [已使用密码]"),
+            None => {}
+        }
+        match &self.error {
+            Some(error) if error.is_limit_exceeded() => {
+                show_str.push_str(&format!(" [超出限制: {}]", error));
+            }
+            Some(error) => show_str.push_str(&format!(" [{}]", error_label(error))),
+            None => {}
+        }
+        if !self.rejected.is_empty() {
+            show_str.push_str(&format!(" ({} 个不安全条目已跳过)", self.rejected.len()));
+        }
+        if self.filtered > 0 {
+            show_str.push_str(&format!(" ({} 个文件被过滤)", self.filtered));
+        }
+        if self.skipped > 0 {
+            show_str.push_str(&format!(" ({} 个已存在的文件被跳过)", self.skipped));
+        }
+
+        let show_path = text(show_str)
+            .width(Length::Fill)
+            .shaping(text::Shaping::Advanced);
+        // 鼠标悬停时显示完整的错误信息
+        let show_path: Element<Message> = match &self.error {
+            Some(error) => tooltip(
+                show_path,
+                container(text(error.to_string()).shaping(text::Shaping::Advanced))
+                    .padding(5)
+                    .style(container::rounded_box),
+                tooltip::Position::Bottom,
+            )
+            .into(),
+            None => show_path.into(),
+        };
+        // 点击压缩包展开内容列表
+        let show_path: Element<Message> = mouse_area(show_path)
+            .on_press(Message::ArchivePreviewToggled((depth, file_id)))
+            .into();
+        let show_path: Element<Message> = if self.password.is_some() {
+            let label = if self.password_revealed {
+                "隐藏密码"
+            } else {
+                "显示密码"
+            };
+            let reveal_button = button(text(label).shaping(text::Shaping::Advanced))
+                .on_press(Message::ArchivePasswordRevealToggled((depth, file_id)));
+
+            row![show_path, reveal_button]
+                .align_y(Alignment::Center)
+                .spacing(5)
+                .into()
+        } else {
+            show_path
+        };
+
+        if self.state == ZipFileHandleState::Running {
+            let progress = progress_bar(0.0..=1.0, self.running_fraction())
+                .width(Length::Fixed(120.0))
+                .height(Length::Fixed(8.0));
+            let mut progress_str = format!("{} 个条目", self.progress.entries);
+            if let Some(throughput) = self.throughput() {
+                progress_str.push_str(&format!(", {}", throughput));
+            }
+
+            return row![start_icon, show_path, progress, text(progress_str)]
+                .align_y(Alignment::Center)
+                .spacing(5)
+                .into();
+        }
+
+        if self.state != ZipFileHandleState::NeedPassword {
+            return row![start_icon, show_path].into();
+        }
+
+        let password_input = text_input("输入密码后回车重试...", &self.password_input)
+            .on_input(move |input| Message::ArchivePasswordChange((depth, file_id, input)))
+            .on_submit(Message::ArchivePasswordSubmit((depth, file_id)))
+            .width(Length::FillPortion(1));
+        let skip_button = button("skip").on_press(Message::ArchivePasswordSkip((depth, file_id)));
+
+        row![start_icon, show_path, password_input, skip_button]
+            .align_y(Alignment::Center)
+            .spacing(5)
+            .into()
+    }
+}
+
+// 内容很多的压缩包只显示前面一部分, 避免界面卡住
+const MAX_PREVIEW_LINES: usize = 500;
+
+// 按路径排序后逐行显示, 目录只在第一次出现时显示一行, 按层级缩进
+fn entry_tree(entries: &[ArchiveEntry]) -> Element<'_, Message> {
+    let mut sorted: Vec<&ArchiveEntry> = entries.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut shown_dirs: HashSet<PathBuf> = HashSet::new();
+    let mut lines = Vec::new();
+
+    for entry in sorted {
+        let components: Vec<_> = entry.path.components().collect();
+
+        let mut dir = PathBuf::new();
+        for (level, component) in components.iter().enumerate() {
+            dir.push(component);
+            let is_last = level + 1 == components.len();
+            if (is_last && !entry.is_dir) || !shown_dirs.insert(dir.clone()) {
+                continue;
+            }
+            lines.push(format!(
+                "{}📁 {}",
+                "    ".repeat(level),
+                component.as_os_str().to_string_lossy()
+            ));
+        }
+
+        if entry.is_dir {
+            continue;
+        }
+
+        let mut line = format!(
+            "{}📄 {}    {} 字节",
+            "    ".repeat(components.len().saturating_sub(1)),
+            entry.path.file_name().unwrap_or_default().to_string_lossy(),
+            entry.size
+        );
+        if let Some(compressed_size) = entry.compressed_size {
+            line.push_str(&format!(" (压缩后 {})", compressed_size));
+        }
+        if let Some(modified) = &entry.modified {
+            line.push_str(&format!("    {}", modified));
+        }
+        if entry.encrypted {
+            line.push_str("    🔒");
+        }
+        lines.push(line);
+    }
+
+    let hidden = lines.len().saturating_sub(MAX_PREVIEW_LINES);
+    let mut tree = Column::with_children(
+        lines
+            .into_iter()
+            .take(MAX_PREVIEW_LINES)
+            .map(|line| text(line).size(14).shaping(text::Shaping::Advanced).into()),
+    );
+    if hidden > 0 {
+        tree = tree.push(text(format!("... 还有 {} 行", hidden)));
+    }
+
+    tree.into()
+}
+
+// 错误的简短说明, 显示在压缩包后面
+fn error_label(error: &Error) -> &'static str {
+    match error {
+        Error::ArchiveError((kind, _, _)) => match kind {
+            ArchiveErrorKind::CorruptHeader => "文件头损坏",
+            ArchiveErrorKind::CrcMismatch => "CRC 校验失败",
+            ArchiveErrorKind::UnsupportedMethod => "不支持的压缩方式",
+            ArchiveErrorKind::MissingVolume => "缺少分卷",
+            ArchiveErrorKind::Truncated => "压缩包不完整",
+            ArchiveErrorKind::Other => "解压失败",
+        },
+        Error::PasswordRequired(_) | Error::WrongPassword(_) => "密码错误",
+        Error::ToolNotFound(_) => "找不到解压程序",
+        Error::DiskFull => "磁盘已满",
+        Error::IoError(_) => "读写错误",
+        Error::FileExists(_) => "文件已存在",
+        _ => "错误",
     }
 }
 
@@ -67,17 +364,31 @@ pub struct ZipFiles {
     output_path: PathBuf,
     zip_files: Vec<ZipFile>,
     depth: usize,
+    options: UnzipOptions,
     pub state: ZipsHandleState,
     finish_count: usize,
+    // 从会话恢复的压缩包状态, 搜索到压缩包后再应用
+    resumed: Vec<ArchiveSession>,
+    // 显示在标题下面的提示, 例如磁盘空间不足
+    warning: Option<String>,
+    // 解压前检查磁盘空间的进度, 已经列出内容的压缩包数和总数
+    space_checked: Option<(usize, usize)>,
 }
 
 #[derive(Clone, Debug)]
 pub enum ZipsHandleState {
+    // 超出最大层数, 这一层不会解压
+    LimitExceeded(Error),
     Searching,
     Zipping,
     Finished,
     Error,
     EmptyZips,
+    WaitingPassword,
+    Cancelled,
+    Paused,
+    // 开始解压前的预览, 只列出内容
+    Listed,
 }
 
 impl fmt::Display for ZipsHandleState {
@@ -88,19 +399,123 @@ impl fmt::Display for ZipsHandleState {
             ZipsHandleState::Finished => write!(f, "完成"),
             ZipsHandleState::Error => write!(f, "错误"),
             ZipsHandleState::EmptyZips => write!(f, "没有压缩文件"),
+            ZipsHandleState::WaitingPassword => write!(f, "等待输入密码"),
+            ZipsHandleState::Cancelled => write!(f, "已取消"),
+            ZipsHandleState::Paused => write!(f, "已暂停"),
+            ZipsHandleState::Listed => write!(f, "预览"),
+            ZipsHandleState::LimitExceeded(e) => write!(f, "超出限制: {}", e),
         }
     }
 }
 
 impl ZipFiles {
-    pub fn new(input_path: PathBuf, output_path: PathBuf, depth: usize) -> Self {
+    pub fn new(
+        input_path: PathBuf,
+        output_path: PathBuf,
+        depth: usize,
+        options: UnzipOptions,
+    ) -> Self {
         Self {
             input_path,
             output_path,
             zip_files: Vec::new(),
             depth,
+            options,
             state: ZipsHandleState::Searching,
             finish_count: 0,
+            resumed: Vec::new(),
+            warning: None,
+            space_checked: None,
+        }
+    }
+
+    // 已经完成的层直接显示, 没有完成的层跳过处理过的压缩包重新开始
+    pub fn from_session(layer: LayerSession, mut options: UnzipOptions) -> Self {
+        options.skip = layer
+            .archives
+            .iter()
+            .filter(|archive| archive.state != ArchiveState::Pending)
+            .map(|archive| archive.archive.path.clone())
+            .collect();
+
+        let mut zip_files = Self::new(layer.input_path, layer.output_path, layer.depth, options);
+        if layer.finished {
+            for archive in &layer.archives {
+                zip_files.push_archive(archive.archive.clone());
+                zip_files.restore_archive(archive);
+            }
+            zip_files.state = ZipsHandleState::Finished;
+        } else {
+            zip_files.resumed = layer.archives;
+        }
+
+        zip_files
+    }
+
+    // 预览时列出的压缩包, 内容已经读出来了, 点击后直接展开
+    pub fn listed(
+        input_path: PathBuf,
+        output_path: PathBuf,
+        options: UnzipOptions,
+        listings: Vec<ArchiveListing>,
+    ) -> Self {
+        let mut zip_files = Self::new(input_path, output_path, 1, options);
+        for listing in listings {
+            zip_files.push_archive(listing.archive);
+            if let Some(zip_file) = zip_files.zip_files.last_mut() {
+                zip_file.state = ZipFileHandleState::Listed;
+                zip_file.listing = Some(listing.entries);
+            }
+        }
+        zip_files.state = if zip_files.zip_files.is_empty() {
+            ZipsHandleState::EmptyZips
+        } else {
+            ZipsHandleState::Listed
+        };
+
+        zip_files
+    }
+
+    pub fn to_session(&self) -> LayerSession {
+        LayerSession {
+            input_path: self.input_path.clone(),
+            output_path: self.output_path.clone(),
+            depth: self.depth,
+            finished: matches!(
+                self.state,
+                ZipsHandleState::Finished | ZipsHandleState::EmptyZips
+            ),
+            archives: self.zip_files.iter().map(ZipFile::to_session).collect(),
+        }
+    }
+
+    pub fn limit_exceeded(&mut self, error: Error) {
+        self.state = ZipsHandleState::LimitExceeded(error);
+    }
+
+    pub fn produced_files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.zip_files
+            .iter()
+            .flat_map(|zip_file| zip_file.produced.iter().cloned())
+    }
+
+    fn push_archive(&mut self, archive: Archive) {
+        self.zip_files
+            .push(ZipFile::new(archive, &self.input_path, &self.output_path));
+    }
+
+    fn restore_archive(&mut self, session: &ArchiveSession) {
+        let Some(zip_file) = self
+            .zip_files
+            .iter_mut()
+            .find(|zip_file| zip_file.archive.path == session.archive.path)
+        else {
+            return;
+        };
+
+        zip_file.restore(session);
+        if zip_file.state != ZipFileHandleState::Waiting {
+            self.finish_count += 1;
         }
     }
 
@@ -108,69 +523,308 @@ impl ZipFiles {
         match self.state {
             ZipsHandleState::Searching | ZipsHandleState::Zipping => match new_progress {
                 Ok(progress) => match progress {
-                    Progress::Finished => self.state = ZipsHandleState::Finished,
-                    Progress::Zipping { file_id, state } => {
-                        match state {
-                            Ok(()) => {
-                                self.zip_files[file_id].state = ZipFileHandleState::Finished;
-                            }
-                            Err(e) => {
-                                self.zip_files[file_id].state = ZipFileHandleState::Error;
-                                println!("Error: {}", e);
-                            }
+                    Progress::Finished => {
+                        self.state = ZipsHandleState::WaitingPassword;
+                        self.check_paused();
+                        self.check_password_finished();
+                    }
+                    Progress::Zipping { file_id, state } => self.file_finished(file_id, state),
+                    Progress::CheckingSpace { checked, total } => {
+                        self.space_checked = Some((checked, total));
+                    }
+                    Progress::Extracting { file_id } => {
+                        if let Some(zip_file) = self.zip_files.get_mut(file_id) {
+                            zip_file.state = ZipFileHandleState::Running;
+                            zip_file.started = Some(Instant::now());
+                            zip_file.progress = ExtractProgress::default();
+                        }
+                    }
+                    Progress::Transferring { file_id, progress } => {
+                        if let Some(zip_file) = self.zip_files.get_mut(file_id) {
+                            zip_file.progress = progress;
                         }
-
-                        self.finish_count += 1;
                     }
                     Progress::EmptyZips => {
                         self.state = ZipsHandleState::EmptyZips;
                     }
                     Progress::Searching { zip_files } => {
                         for zip_file in zip_files {
-                            self.zip_files
-                                .push(ZipFile::new(zip_file, self.input_path.clone()));
+                            self.push_archive(zip_file);
+                        }
+                        for archive in std::mem::take(&mut self.resumed) {
+                            self.restore_archive(&archive);
                         }
                         self.state = ZipsHandleState::Zipping;
                     }
                 },
-                Err(_error) => self.state = ZipsHandleState::Error,
+                Err(Error::InsufficientSpace((required, available))) => {
+                    self.warning = Some(format!(
+                        "磁盘空间不足: 需要 {} MB, 剩余 {} MB",
+                        required / (1024 * 1024),
+                        available / (1024 * 1024)
+                    ));
+                    self.state = ZipsHandleState::Paused;
+                }
+                Err(error) => {
+                    self.warning = Some(error.to_string());
+                    self.state = ZipsHandleState::Error;
+                }
             },
             _ => {}
         }
     }
 
+    fn file_finished(&mut self, file_id: usize, state: Result<ExtractOutcome, Error>) {
+        let Some(zip_file) = self.zip_files.get_mut(file_id) else {
+            return;
+        };
+
+        match state {
+            Ok(outcome) => {
+                zip_file.state = ZipFileHandleState::Finished;
+                zip_file.password = outcome.password;
+                zip_file.produced = outcome.report.files;
+                zip_file.rejected = outcome.report.rejected;
+                zip_file.filtered = outcome.report.filtered;
+                zip_file.skipped = outcome.report.skipped;
+            }
+            Err(Error::PasswordRequired(_) | Error::WrongPassword(_)) => {
+                zip_file.state = ZipFileHandleState::NeedPassword;
+                zip_file.password_input.clear();
+                return;
+            }
+            Err(Error::Cancelled) => {
+                zip_file.state = ZipFileHandleState::Cancelled;
+                return;
+            }
+            Err(Error::Paused) => {
+                zip_file.state = ZipFileHandleState::Waiting;
+                return;
+            }
+            Err(e) => {
+                zip_file.state = ZipFileHandleState::Error;
+                zip_file.error = Some(e);
+            }
+        }
+
+        self.finish_count += 1;
+    }
+
+    // 其余压缩包都解压完后, 等到需要密码的压缩包也处理完, 这一层才算完成
+    fn check_password_finished(&mut self) {
+        let pending = self.zip_files.iter().any(|zip_file| {
+            matches!(
+                zip_file.state,
+                ZipFileHandleState::NeedPassword
+                    | ZipFileHandleState::Waiting
+                    | ZipFileHandleState::Running
+            )
+        });
+
+        if matches!(self.state, ZipsHandleState::WaitingPassword) && !pending {
+            self.state = ZipsHandleState::Finished;
+        }
+    }
+
+    // 暂停后还有没解压的压缩包, 这一层停在暂停状态
+    fn check_paused(&mut self) {
+        let waiting = self
+            .zip_files
+            .iter()
+            .any(|zip_file| zip_file.state == ZipFileHandleState::Waiting);
+
+        if self.options.pause.is_cancelled() && waiting {
+            self.state = ZipsHandleState::Paused;
+        }
+    }
+
+    // 正在解压的压缩包会继续解压完, 之后这一层才会进入暂停状态
+    pub fn pause(&mut self) {
+        if !self.is_running() {
+            return;
+        }
+
+        self.options.pause.cancel();
+        if matches!(self.state, ZipsHandleState::WaitingPassword) {
+            self.state = ZipsHandleState::Paused;
+        }
+    }
+
+    pub fn password_input(&mut self, file_id: usize, input: String) {
+        if let Some(zip_file) = self.zip_files.get_mut(file_id) {
+            zip_file.password_input = input;
+        }
+    }
+
+    pub fn retry_password(&mut self, file_id: usize) -> Task<Message> {
+        let Some(zip_file) = self.zip_files.get_mut(file_id) else {
+            return Task::none();
+        };
+        if zip_file.state != ZipFileHandleState::NeedPassword || zip_file.password_input.is_empty()
+        {
+            return Task::none();
+        }
+
+        zip_file.state = ZipFileHandleState::Running;
+
+        let depth = self.depth;
+        Task::perform(
+            retry_archive(
+                zip_file.archive.clone(),
+                zip_file.output_dir.clone(),
+                zip_file.password_input.clone(),
+                self.options.clone(),
+            ),
+            move |result| Message::ArchivePasswordRetried((depth, file_id, result)),
+        )
+    }
+
+    pub fn password_retried(&mut self, file_id: usize, result: Result<ExtractOutcome, Error>) {
+        self.file_finished(file_id, result);
+        self.check_password_finished();
+    }
+
+    pub fn skip_password(&mut self, file_id: usize) {
+        if let Some(zip_file) = self.zip_files.get_mut(file_id) {
+            if zip_file.state == ZipFileHandleState::NeedPassword {
+                zip_file.state = ZipFileHandleState::Error;
+                self.finish_count += 1;
+            }
+        }
+        self.check_password_finished();
+    }
+
+    pub fn toggle_password_reveal(&mut self, file_id: usize) {
+        if let Some(zip_file) = self.zip_files.get_mut(file_id) {
+            zip_file.password_revealed = !zip_file.password_revealed;
+        }
+    }
+
+    pub fn toggle_preview(&mut self, file_id: usize) -> Task<Message> {
+        let Some(zip_file) = self.zip_files.get_mut(file_id) else {
+            return Task::none();
+        };
+
+        zip_file.expanded = !zip_file.expanded;
+        if !zip_file.expanded || zip_file.listing.is_some() {
+            return Task::none();
+        }
+
+        let depth = self.depth;
+        Task::perform(list_archive(zip_file.archive.clone()), move |result| {
+            Message::ArchivePreviewLoaded((depth, file_id, result))
+        })
+    }
+
+    pub fn preview_loaded(&mut self, file_id: usize, result: Result<Vec<ArchiveEntry>, Error>) {
+        if let Some(zip_file) = self.zip_files.get_mut(file_id) {
+            zip_file.listing = Some(result);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self.state,
+            ZipsHandleState::Searching
+                | ZipsHandleState::Zipping
+                | ZipsHandleState::WaitingPassword
+        )
+    }
+
+    // 取消后订阅被移除, 后台还在运行的解压进程由 cancel token 杀掉
+    pub fn cancel(&mut self) {
+        if !self.is_running() {
+            return;
+        }
+
+        self.options.cancel.cancel();
+        self.state = ZipsHandleState::Cancelled;
+
+        for zip_file in &mut self.zip_files {
+            if matches!(
+                zip_file.state,
+                ZipFileHandleState::Waiting
+                    | ZipFileHandleState::Running
+                    | ZipFileHandleState::NeedPassword
+            ) {
+                zip_file.state = ZipFileHandleState::Cancelled;
+            }
+        }
+    }
+
     pub fn subscription(&self) -> Subscription<Message> {
         match self.state {
             ZipsHandleState::Searching | ZipsHandleState::Zipping => run_zip_dir(
                 self.depth,
                 self.input_path.clone(),
                 self.output_path.clone(),
-                None,
+                self.options.clone(),
             )
             .map(Message::ZipFileHandleProgress),
             _ => Subscription::none(),
         }
     }
 
-    pub fn view(&self) -> Element<Message> {
+    pub fn view(&self) -> Element<'_, Message> {
         // TODO: 每一层输出目录提供打开和复制
-        let title_str = format!("第 {} 层: {}", self.depth, self.state.to_string(),);
+        let mut title_str = format!("第 {} 层: {}", self.depth, self.state);
+        let limit_count = self
+            .zip_files
+            .iter()
+            .filter(|zip_file| zip_file.is_limit_exceeded())
+            .count();
+        if limit_count > 0 {
+            title_str.push_str(&format!(" ({} 个超出限制)", limit_count));
+        }
+        if let Some((checked, total)) = self
+            .space_checked
+            .filter(|(checked, total)| checked < total)
+        {
+            title_str.push_str(&format!(" (检查磁盘空间 {}/{})", checked, total));
+        }
 
         let path_str = format!("{}", self.input_path.display());
 
+        let mut cancel_button = button("cancel");
+        if self.is_running() {
+            cancel_button = cancel_button.on_press(Message::LayerCancel(self.depth));
+        }
+
+        // 正在解压的压缩包按字节进度计入, 一个大压缩包也能看到整层的进度
+        let layer_progress = self.finish_count as f32
+            + self
+                .zip_files
+                .iter()
+                .map(ZipFile::running_fraction)
+                .sum::<f32>();
+
         let deepth_title = row![
             text(title_str).shaping(text::Shaping::Advanced),
-            progress_bar(0.0..=self.zip_files.len() as f32, self.finish_count as f32),
+            progress_bar(0.0..=self.zip_files.len() as f32, layer_progress),
             text(format!("{}/{}", self.finish_count, self.zip_files.len()))
-                .shaping(text::Shaping::Advanced)
+                .shaping(text::Shaping::Advanced),
+            cancel_button
         ]
         .align_y(Alignment::Center)
         .spacing(3);
 
         let deepth_path = text(path_str).shaping(text::Shaping::Advanced);
+        let warning = self
+            .warning
+            .as_ref()
+            .map(|warning| text(format!("⚠ {}", warning)).shaping(text::Shaping::Advanced));
 
-        let zip_files = Column::with_children(self.zip_files.iter().map(ZipFile::view)).spacing(5);
+        let zip_files = Column::with_children(
+            self.zip_files
+                .iter()
+                .enumerate()
+                .map(|(file_id, zip_file)| zip_file.view(self.depth, file_id)),
+        )
+        .spacing(5);
 
-        column![deepth_title, deepth_path, zip_files].into()
+        column![deepth_title, deepth_path]
+            .push_maybe(warning)
+            .push(zip_files)
+            .into()
     }
 }
@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use clap::{Parser, ValueEnum};
+use futures::StreamExt;
+use serde_json::json;
+
+use zipdive::backend::ExtractProgress;
+use zipdive::zip::{
+    collect_compressed_files, list_dir, parse_date, read_password_file, unzip_dir_s, Archive,
+    ArchiveListing, ConflictPolicy, DetectPolicy, EntryFilter, ExtractOutcome, Layout, Limits,
+    Progress, ScanPolicy, UnzipOptions,
+};
+use zipdive::Error;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DetectArg {
+    Extension,
+    Magic,
+    Both,
+}
+
+impl From<DetectArg> for DetectPolicy {
+    fn from(detect: DetectArg) -> Self {
+        match detect {
+            DetectArg::Extension => DetectPolicy::Extension,
+            DetectArg::Magic => DetectPolicy::MagicBytes,
+            DetectArg::Both => DetectPolicy::Both,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LayoutArg {
+    Layered,
+    Nested,
+}
+
+impl From<LayoutArg> for Layout {
+    fn from(layout: LayoutArg) -> Self {
+        match layout {
+            LayoutArg::Layered => Layout::Layered,
+            LayoutArg::Nested => Layout::Nested,
+        }
+    }
+}
+
+fn parse_date_arg(date: &str) -> Result<u64, String> {
+    parse_date(date).ok_or_else(|| format!("invalid date `{date}`, expected YYYY-MM-DD"))
+}
+
+// 0 层什么都不会解压
+fn parse_depth_arg(depth: &str) -> Result<usize, String> {
+    match depth.parse() {
+        Ok(0) | Err(_) => Err(format!("invalid depth `{depth}`, expected at least 1")),
+        Ok(depth) => Ok(depth),
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ConflictArg {
+    Overwrite,
+    Skip,
+    RenameNew,
+    RenameExisting,
+    Fail,
+}
+
+impl From<ConflictArg> for ConflictPolicy {
+    fn from(conflict: ConflictArg) -> Self {
+        match conflict {
+            ConflictArg::Overwrite => ConflictPolicy::Overwrite,
+            ConflictArg::Skip => ConflictPolicy::Skip,
+            ConflictArg::RenameNew => ConflictPolicy::RenameNew,
+            ConflictArg::RenameExisting => ConflictPolicy::RenameExisting,
+            ConflictArg::Fail => ConflictPolicy::Fail,
+        }
+    }
+}
+
+/// 不启动界面, 一层一层地递归解压目录中的压缩文件
+#[derive(Debug, Parser)]
+#[command(name = "zipdive-cli", version)]
+struct Args {
+    /// 压缩文件目录
+    source: PathBuf,
+
+    /// 解压到的目录
+    #[arg(required_unless_present = "list")]
+    target: Option<PathBuf>,
+
+    /// 只列出 source 中每个压缩包的内容, 不解压
+    #[arg(long)]
+    list: bool,
+
+    /// 候选密码, 可以指定多次
+    #[arg(short, long = "password")]
+    passwords: Vec<String>,
+
+    /// 密码文件, 每行一个密码
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+
+    /// 最多解压的层数, 默认 32 层
+    #[arg(long, value_parser = parse_depth_arg)]
+    max_depth: Option<usize>,
+
+    /// 整个任务最多解压出来的字节数
+    #[arg(long)]
+    max_bytes: Option<u64>,
+
+    /// 单个压缩包解压后大小和压缩包大小的最大比值, 默认 1000
+    #[arg(long)]
+    max_ratio: Option<f64>,
+
+    /// 整个任务最多解压出来的文件数
+    #[arg(long)]
+    max_files: Option<usize>,
+
+    /// 解压前不检查目标磁盘的剩余空间
+    #[arg(long)]
+    ignore_disk_space: bool,
+
+    /// 不使用默认的层数和压缩比限制, 只使用上面指定的限制
+    #[arg(long)]
+    no_default_limits: bool,
+
+    /// 每一层同时解压的压缩包数量, 默认为 CPU 核心数
+    #[arg(short = 'j', long)]
+    concurrency: Option<usize>,
+
+    /// 压缩文件的识别方式
+    #[arg(long, value_enum, default_value_t = DetectArg::Extension)]
+    detect: DetectArg,
+
+    /// 输出目录的结构, layered 时第 N 层解压到 target/N, nested 时解压到压缩包所在位置
+    #[arg(long, value_enum, default_value_t = LayoutArg::Layered)]
+    layout: LayoutArg,
+
+    /// nested 时删除解压成功的中间压缩包, 不会删除 source 中的压缩包
+    #[arg(long)]
+    delete_archives: bool,
+
+    /// 输出目录中已有同名文件时的处理方式, rename-new 时新文件改名为 `file (1).txt`
+    #[arg(long, value_enum, default_value_t = ConflictArg::Overwrite)]
+    conflict: ConflictArg,
+
+    /// 只解压匹配的条目, 例如 `*.log` 或 `*.{log,json}`, 可以指定多次
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// 不解压匹配的条目, 优先于 --include, 可以指定多次
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// 查找压缩包时跳过的目录名, 例如 `.git`, 可以指定多次或用逗号分隔
+    #[arg(long, value_delimiter = ',')]
+    exclude_dir: Vec<String>,
+
+    /// 只解压不小于这个大小的压缩包, 单位字节
+    #[arg(long)]
+    min_size: Option<u64>,
+
+    /// 只解压不大于这个大小的压缩包, 单位字节
+    #[arg(long)]
+    max_size: Option<u64>,
+
+    /// 只解压这一天及之后修改的压缩包, 格式 YYYY-MM-DD
+    #[arg(long, value_parser = parse_date_arg)]
+    modified_after: Option<u64>,
+
+    /// 只解压这一天之前修改的压缩包, 格式 YYYY-MM-DD
+    #[arg(long, value_parser = parse_date_arg)]
+    modified_before: Option<u64>,
+
+    /// 查找压缩包时跟随符号链接
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// 输出格式, json 时每行输出一个事件
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// 在输出中显示解压成功时使用的密码, 默认只标记用了密码
+    #[arg(long)]
+    show_password: bool,
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    succeeded: usize,
+    failed: usize,
+    // 因为超出最大层数而停止
+    depth_exceeded: bool,
+}
+
+struct Reporter {
+    format: OutputFormat,
+    show_password: bool,
+    // stdout 是终端时在 stderr 上刷新正在解压的压缩包的进度
+    live: bool,
+}
+
+impl Reporter {
+    fn transferring(&self, archive: &Archive, progress: &ExtractProgress, started: Instant) {
+        if !self.live {
+            return;
+        }
+
+        let elapsed = started.elapsed().as_secs_f64();
+        let megabytes = progress.bytes as f64 / (1024.0 * 1024.0);
+        let mut line = format!("  … {}", archive.path.display());
+        if let Some(fraction) = progress.fraction() {
+            line.push_str(&format!(" {:.0}%", fraction * 100.0));
+        }
+        line.push_str(&format!(" {:.1} MB", megabytes));
+        if elapsed > 0.0 {
+            line.push_str(&format!(" {:.1} MB/s", megabytes / elapsed));
+        }
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+
+    fn checking_space(&self, checked: usize, total: usize) {
+        if !self.live {
+            return;
+        }
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[2K  … 检查磁盘空间 {}/{}", checked, total);
+        let _ = stderr.flush();
+    }
+
+    // 输出新的一行之前先清掉进度行
+    fn clear_live(&self) {
+        if self.live {
+            eprint!("\r\x1b[2K");
+        }
+    }
+
+    fn layer(&self, depth: usize, input_path: &Path, archive_count: usize) {
+        match self.format {
+            OutputFormat::Text => println!(
+                "第 {} 层: {} ({} 个压缩文件)",
+                depth,
+                input_path.display(),
+                archive_count
+            ),
+            OutputFormat::Json => println!(
+                "{}",
+                json!({
+                    "event": "layer",
+                    "depth": depth,
+                    "input": input_path,
+                    "archives": archive_count,
+                })
+            ),
+        }
+    }
+
+    fn archive(&self, depth: usize, archive: &Archive, state: &Result<ExtractOutcome, Error>) {
+        self.clear_live();
+        let password =
+            |outcome: &ExtractOutcome| outcome.password.clone().filter(|_| self.show_password);
+
+        match self.format {
+            OutputFormat::Text => match state {
+                Ok(outcome) => {
+                    let mut line = format!("  ✔ {}", archive.path.display());
+                    match (&outcome.password, self.show_password) {
+                        (Some(password), true) => line.push_str(&format!(" [密码: {}]", password)),
+                        (Some(_), false) => line.push_str(" [已使用密码]"),
+                        (None, _) => {}
+                    }
+                    if !outcome.report.rejected.is_empty() {
+                        line.push_str(&format!(
+                            " ({} 个不安全条目已跳过)",
+                            outcome.report.rejected.len()
+                        ));
+                    }
+                    if outcome.report.filtered > 0 {
+                        line.push_str(&format!(" ({} 个文件被过滤)", outcome.report.filtered));
+                    }
+                    if outcome.report.skipped > 0 {
+                        line.push_str(&format!(
+                            " ({} 个已存在的文件被跳过)",
+                            outcome.report.skipped
+                        ));
+                    }
+                    println!("{}", line);
+                }
+                Err(e) => println!("  ✘ {}: {}", archive.path.display(), e),
+            },
+            OutputFormat::Json => println!(
+                "{}",
+                json!({
+                    "event": "archive",
+                    "depth": depth,
+                    "path": archive.path,
+                    "ok": state.is_ok(),
+                    "password_used": state.as_ref().ok().map(|outcome| outcome.password.is_some()),
+                    "password": state.as_ref().ok().and_then(password),
+                    "files": state.as_ref().ok().map(|outcome| outcome.report.files.len()),
+                    "rejected": state.as_ref().ok().map(|outcome| &outcome.report.rejected),
+                    "filtered": state.as_ref().ok().map(|outcome| outcome.report.filtered),
+                    "skipped": state.as_ref().ok().map(|outcome| outcome.report.skipped),
+                    "error": state.as_ref().err().map(|e| e.to_string()),
+                })
+            ),
+        }
+    }
+
+    fn listing(&self, listing: &ArchiveListing) {
+        match self.format {
+            OutputFormat::Text => {
+                println!("{}", listing.archive.path.display());
+                match &listing.entries {
+                    Ok(entries) => {
+                        for entry in entries {
+                            println!(
+                                "  {:>12} {:>12} {:19} {}{}",
+                                entry.size,
+                                entry
+                                    .compressed_size
+                                    .map(|size| size.to_string())
+                                    .unwrap_or_default(),
+                                entry.modified.as_deref().unwrap_or_default(),
+                                entry.path.display(),
+                                if entry.encrypted { " [加密]" } else { "" }
+                            );
+                        }
+                    }
+                    Err(e) => println!("  ✘ {}", e),
+                }
+            }
+            OutputFormat::Json => println!(
+                "{}",
+                json!({
+                    "event": "listing",
+                    "path": listing.archive.path,
+                    "ok": listing.entries.is_ok(),
+                    "entries": listing.entries.as_ref().ok().map(|entries| {
+                        entries
+                            .iter()
+                            .map(|entry| {
+                                json!({
+                                    "path": entry.path,
+                                    "size": entry.size,
+                                    "compressed_size": entry.compressed_size,
+                                    "modified": entry.modified,
+                                    "is_dir": entry.is_dir,
+                                    "encrypted": entry.encrypted,
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                    }),
+                    "error": listing.entries.as_ref().err().map(|e| e.to_string()),
+                })
+            ),
+        }
+    }
+
+    fn summary(&self, summary: &Summary) {
+        match self.format {
+            OutputFormat::Text => println!(
+                "完成: {} 个成功, {} 个失败",
+                summary.succeeded, summary.failed
+            ),
+            OutputFormat::Json => println!(
+                "{}",
+                json!({
+                    "event": "summary",
+                    "succeeded": summary.succeeded,
+                    "failed": summary.failed,
+                })
+            ),
+        }
+    }
+
+    fn error(&self, error: &Error) {
+        self.clear_live();
+        match self.format {
+            OutputFormat::Text => eprintln!("错误: {}", error),
+            OutputFormat::Json => println!(
+                "{}",
+                json!({
+                    "event": "error",
+                    "message": error.to_string(),
+                })
+            ),
+        }
+    }
+}
+
+// 和界面一样一层一层地解压, 每一层的目录由 layout 决定
+async fn run_layers(
+    args: &Args,
+    target: &Path,
+    mut options: UnzipOptions,
+    reporter: &Reporter,
+) -> Result<Summary, Error> {
+    std::fs::create_dir_all(target)?;
+
+    let layout = Layout::from(args.layout);
+    let mut summary = Summary::default();
+    let mut depth = 1;
+
+    loop {
+        // 超出最大层数时, 只有上一层还解压出了压缩包才算超出限制, 第一层直接算超出
+        if let Err(e) = options.limits.check_depth(depth) {
+            let candidates = options.candidates.as_deref().unwrap_or_default();
+            if depth == 1 || !collect_compressed_files(candidates, options.detect_policy).is_empty()
+            {
+                reporter.error(&e);
+                summary.depth_exceeded = true;
+            }
+            break;
+        }
+
+        let (input_path, output_path) = layout.layer_paths(&args.source, target, depth);
+        options.delete_archives = args.delete_archives && layout == Layout::Nested && depth > 1;
+
+        let mut archives = Vec::new();
+        let mut started = HashMap::new();
+        let mut produced = Vec::new();
+        let mut empty_zips = false;
+        let mut progress_stream = pin!(unzip_dir_s(
+            input_path.clone(),
+            output_path.clone(),
+            options.clone()
+        ));
+
+        while let Some(progress) = progress_stream.next().await {
+            match progress? {
+                Progress::EmptyZips => empty_zips = true,
+                Progress::Searching { zip_files } => {
+                    reporter.layer(depth, &input_path, zip_files.len());
+                    archives = zip_files;
+                }
+                Progress::Zipping { file_id, state } => {
+                    if state.is_ok() {
+                        summary.succeeded += 1;
+                    } else {
+                        summary.failed += 1;
+                    }
+
+                    if let Some(archive) = archives.get(file_id) {
+                        reporter.archive(depth, archive, &state);
+                    }
+                    if let Ok(outcome) = state {
+                        produced.extend(outcome.report.files);
+                    }
+                }
+                Progress::CheckingSpace { checked, total } => {
+                    reporter.checking_space(checked, total);
+                }
+                Progress::Extracting { file_id } => {
+                    started.insert(file_id, Instant::now());
+                }
+                Progress::Transferring { file_id, progress } => {
+                    if let Some(archive) = archives.get(file_id) {
+                        let started = *started.entry(file_id).or_insert_with(Instant::now);
+                        reporter.transferring(archive, &progress, started);
+                    }
+                }
+                Progress::Finished => {}
+            }
+        }
+
+        if empty_zips {
+            break;
+        }
+
+        // 下一层只处理这一层解压出来的文件
+        options.candidates = Some(produced);
+        depth += 1;
+    }
+
+    Ok(summary)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let reporter = Reporter {
+        format: args.format,
+        show_password: args.show_password,
+        live: matches!(args.format, OutputFormat::Text) && std::io::stdout().is_terminal(),
+    };
+
+    let mut passwords = args.passwords.clone();
+    if let Some(password_file) = &args.password_file {
+        match read_password_file(password_file) {
+            Ok(file_passwords) => passwords.extend(file_passwords),
+            Err(e) => {
+                reporter.error(&e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut limits = if args.no_default_limits {
+        Limits::unlimited()
+    } else {
+        Limits::default()
+    };
+    limits.max_depth = args.max_depth.or(limits.max_depth);
+    limits.max_total_bytes = args.max_bytes.or(limits.max_total_bytes);
+    limits.max_ratio = args.max_ratio.or(limits.max_ratio);
+    limits.max_files = args.max_files.or(limits.max_files);
+
+    let filter = EntryFilter {
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+    };
+    if let Err(e) = filter.matcher() {
+        reporter.error(&e);
+        return ExitCode::FAILURE;
+    }
+
+    let scan = ScanPolicy {
+        exclude_dirs: args.exclude_dir.clone(),
+        min_size: args.min_size,
+        max_size: args.max_size,
+        modified_after: args.modified_after,
+        modified_before: args.modified_before,
+        follow_symlinks: args.follow_symlinks,
+    };
+    if let Err(e) = scan.matcher() {
+        reporter.error(&e);
+        return ExitCode::FAILURE;
+    }
+
+    let options = UnzipOptions {
+        passwords,
+        filter,
+        scan,
+        conflict: args.conflict.into(),
+        detect_policy: args.detect.into(),
+        concurrency: args.concurrency,
+        limits,
+        ignore_disk_space: args.ignore_disk_space,
+        ..Default::default()
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            reporter.error(&e.into());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.list {
+        return match runtime.block_on(list_dir(
+            args.source.clone(),
+            options.detect_policy,
+            options.scan.clone(),
+        )) {
+            Ok(listings) => {
+                listings
+                    .iter()
+                    .for_each(|listing| reporter.listing(listing));
+                // 有压缩包列不出来时和解压失败一样返回失败
+                if listings.iter().any(|listing| listing.entries.is_err()) {
+                    ExitCode::FAILURE
+                } else {
+                    ExitCode::SUCCESS
+                }
+            }
+            Err(e) => {
+                reporter.error(&e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    // 不是 --list 时 clap 保证有 target
+    let target = args.target.clone().unwrap_or_default();
+    match runtime.block_on(run_layers(&args, &target, options, &reporter)) {
+        Ok(summary) => {
+            reporter.summary(&summary);
+            if summary.failed > 0 || summary.depth_exceeded {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(e) => {
+            reporter.error(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,21 +1,151 @@
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+// 压缩包本身的问题, 由各个后端根据错误信息归类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveErrorKind {
+    CorruptHeader,
+    CrcMismatch,
+    UnsupportedMethod,
+    MissingVolume,
+    Truncated,
+    Other,
+}
+
+impl ArchiveErrorKind {
+    // 进程内后端读取数据出错时, 根据 io::Error 归类
+    pub fn from_io(e: &std::io::Error) -> Self {
+        let message = io_message(e).to_lowercase();
+
+        if e.kind() == std::io::ErrorKind::UnexpectedEof || message.contains("unexpected end") {
+            ArchiveErrorKind::Truncated
+        } else if message.contains("checksum") || message.contains("crc") {
+            ArchiveErrorKind::CrcMismatch
+        } else if message.contains("header") {
+            ArchiveErrorKind::CorruptHeader
+        } else {
+            ArchiveErrorKind::Other
+        }
+    }
+}
+
+// tar 等库会把底层错误包一层, 把整条错误链拼起来才能看到原因
+// 后端读取数据时放进 io::Error 里的错误, 例如取消, 需要原样取出来
+fn wrapped_error(e: &std::io::Error) -> Option<Error> {
+    let mut current: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(error) = current {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            return Some(error.clone());
+        }
+        // io::Error 的 source 会跳过它包着的错误本身
+        current = match error.downcast_ref::<std::io::Error>() {
+            Some(io_error) => io_error
+                .get_ref()
+                .map(|inner| inner as &(dyn std::error::Error + 'static)),
+            None => error.source(),
+        };
+    }
+
+    None
+}
+
+fn io_message(e: &std::io::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(error) = source {
+        message.push_str(&format!(": {}", error));
+        source = error.source();
+    }
+
+    message
+}
+
+impl fmt::Display for ArchiveErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveErrorKind::CorruptHeader => write!(f, "corrupt header"),
+            ArchiveErrorKind::CrcMismatch => write!(f, "crc mismatch"),
+            ArchiveErrorKind::UnsupportedMethod => write!(f, "unsupported method"),
+            ArchiveErrorKind::MissingVolume => write!(f, "missing volume"),
+            ArchiveErrorKind::Truncated => write!(f, "truncated archive"),
+            ArchiveErrorKind::Other => write!(f, "archive error"),
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum Error {
     SystemNotSupport,
     FileNotExists(PathBuf),
     SearchFailed,
     IoError(String),
-    ZipError((String, PathBuf)),
+    // 错误类型, 压缩包路径, 后端给出的详细信息
+    ArchiveError((ArchiveErrorKind, PathBuf, String)),
+    PasswordRequired(PathBuf),
+    // 提供了密码, 但是密码不对
+    WrongPassword(PathBuf),
+    // 找不到外部解压程序
+    ToolNotFound(String),
+    DiskFull,
+    Cancelled,
+    Paused,
+    DepthLimitExceeded(usize),
+    SizeLimitExceeded(u64),
+    CompressionRatioExceeded((PathBuf, f64)),
+    FileCountExceeded(usize),
+    // 需要的字节数, 剩余的字节数
+    InsufficientSpace((u64, u64)),
+    // 过滤条目的 glob 模式写错了
+    InvalidPattern(String),
+    // ConflictPolicy::Fail 时输出目录中已经有同名文件
+    FileExists(PathBuf),
+}
+
+impl Error {
+    // 超出 Limits 中的某个限制
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self,
+            Error::DepthLimitExceeded(_)
+                | Error::SizeLimitExceeded(_)
+                | Error::CompressionRatioExceeded(_)
+                | Error::FileCountExceeded(_)
+        )
+    }
+
+    // 读取压缩包数据时出错, 除了磁盘写满都算作压缩包本身的问题
+    pub fn from_archive_io(e: std::io::Error, path: &Path) -> Self {
+        if let Some(error) = wrapped_error(&e) {
+            return error;
+        }
+
+        match e.kind() {
+            std::io::ErrorKind::StorageFull => Error::DiskFull,
+            _ => Error::ArchiveError((
+                ArchiveErrorKind::from_io(&e),
+                path.to_path_buf(),
+                io_message(&e),
+            )),
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
-        Error::IoError(e.to_string())
+        if let Some(error) = wrapped_error(&e) {
+            return error;
+        }
+
+        match e.kind() {
+            std::io::ErrorKind::StorageFull => Error::DiskFull,
+            _ => Error::IoError(e.to_string()),
+        }
     }
 }
 
+impl std::error::Error for Error {}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -23,7 +153,32 @@ impl fmt::Display for Error {
             Error::FileNotExists(path) => write!(f, "file not exists: {:?}", path),
             Error::SearchFailed => write!(f, "search failed"),
             Error::IoError(e) => write!(f, "io error: {}", e),
-            Error::ZipError((e, path)) => write!(f, "zip error: {}, path: {:?}", e, path),
+            Error::ArchiveError((kind, path, detail)) => {
+                write!(f, "{}: {}, path: {:?}", kind, detail.trim(), path)
+            }
+            Error::PasswordRequired(path) => write!(f, "password required: {:?}", path),
+            Error::WrongPassword(path) => write!(f, "wrong password: {:?}", path),
+            Error::ToolNotFound(tool) => write!(f, "tool not found: {}", tool),
+            Error::DiskFull => write!(f, "disk full"),
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::Paused => write!(f, "paused"),
+            Error::DepthLimitExceeded(max) => write!(f, "depth limit exceeded: {}", max),
+            Error::SizeLimitExceeded(max) => write!(f, "size limit exceeded: {} bytes", max),
+            Error::CompressionRatioExceeded((path, ratio)) => {
+                write!(
+                    f,
+                    "compression ratio exceeded: {:.1}, path: {:?}",
+                    ratio, path
+                )
+            }
+            Error::FileCountExceeded(max) => write!(f, "file count limit exceeded: {}", max),
+            Error::InvalidPattern(e) => write!(f, "invalid pattern: {}", e),
+            Error::FileExists(path) => write!(f, "file already exists: {:?}", path),
+            Error::InsufficientSpace((required, available)) => write!(
+                f,
+                "insufficient disk space: {} bytes required, {} bytes available",
+                required, available
+            ),
         }
     }
 }
@@ -1,3 +1,7 @@
+#[cfg(feature = "gui")]
 pub mod app;
 mod error;
-mod zip;
+pub mod zip;
+
+pub use error::{ArchiveErrorKind, Error};
+pub use zip::backend;
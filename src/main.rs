@@ -8,7 +8,7 @@ use zipdive::app::ZipDive;
 fn main() -> iced::Result {
     let settings = Settings {
         default_font: Font {
-            family: Family::Name("LXGW WenKai".into()),
+            family: Family::Name("LXGW WenKai"),
             weight: Weight::Normal,
             ..Default::default()
         },
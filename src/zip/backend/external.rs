@@ -0,0 +1,668 @@
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{ArchiveErrorKind, Error};
+use crate::zip::detect::{self, ArchiveFormat};
+use crate::zip::filter::EntryFilter;
+
+use super::safety::{is_safe_entry_path, link_escapes};
+use super::{
+    ArchiveBackend, ArchiveEntry, CancelToken, ExtractOptions, ExtractReport, ProgressSink,
+};
+
+fn is_known_archive(file: &Path) -> bool {
+    detect::detect_format(file).is_some_and(|format| format != ArchiveFormat::Zstd)
+}
+
+// 7z 和 Bandizip 的错误提示, 按顺序匹配, 用来给失败的输出归类
+const ERROR_MESSAGES: [(&str, ArchiveErrorKind); 9] = [
+    ("CRC Failed", ArchiveErrorKind::CrcMismatch),
+    ("Data Error", ArchiveErrorKind::CrcMismatch),
+    ("Unsupported Method", ArchiveErrorKind::UnsupportedMethod),
+    ("Missing volume", ArchiveErrorKind::MissingVolume),
+    ("Unexpected end of archive", ArchiveErrorKind::Truncated),
+    ("Unavailable data", ArchiveErrorKind::Truncated),
+    ("Headers Error", ArchiveErrorKind::CorruptHeader),
+    (
+        "Can not open the file as archive",
+        ArchiveErrorKind::CorruptHeader,
+    ),
+    ("Is not archive", ArchiveErrorKind::CorruptHeader),
+];
+
+// 不同系统上磁盘写满的提示
+const DISK_FULL_MESSAGES: [&str; 2] = ["No space left on device", "not enough space on the disk"];
+
+fn classify_output(message: &str, file_path: &Path) -> Error {
+    if DISK_FULL_MESSAGES
+        .iter()
+        .any(|disk_full| message.contains(disk_full))
+    {
+        return Error::DiskFull;
+    }
+
+    let kind = ERROR_MESSAGES
+        .iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map(|(_, kind)| *kind)
+        .unwrap_or(ArchiveErrorKind::Other);
+
+    Error::ArchiveError((kind, file_path.to_path_buf(), message.trim().to_string()))
+}
+
+fn check_output(output: Output, file_path: &Path) -> Result<Output, Error> {
+    if output.status.success() {
+        return Ok(output);
+    }
+
+    // 7z 把一部分错误写到 stdout, 两个都要看
+    let message = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    Err(classify_output(&message, file_path))
+}
+
+// 运行外部程序并等待结束, 取消时杀掉子进程
+fn run_cancellable(command: &mut Command, cancel: &CancelToken) -> Result<Output, Error> {
+    run_cancellable_with(command, cancel, |_| {})
+}
+
+// 和 run_cancellable 一样, 另外每读到一段 stdout 就交给 on_stdout, 用来解析进度
+fn run_cancellable_with(
+    command: &mut Command,
+    cancel: &CancelToken,
+    on_stdout: impl FnMut(&[u8]) + Send + 'static,
+) -> Result<Output, Error> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                Error::ToolNotFound(command.get_program().to_string_lossy().to_string())
+            }
+            _ => Error::from(e),
+        })?;
+
+    // 在单独的线程中读取输出, 避免管道写满后子进程卡住
+    let stdout = child
+        .stdout
+        .take()
+        .map(|stdout| read_to_end_thread(stdout, on_stdout));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| read_to_end_thread(stderr, |_| {}));
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error::Cancelled);
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let join = |handle: Option<thread::JoinHandle<Vec<u8>>>| {
+        handle
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    };
+
+    Ok(Output {
+        status,
+        stdout: join(stdout),
+        stderr: join(stderr),
+    })
+}
+
+fn read_to_end_thread(
+    mut reader: impl Read + Send + 'static,
+    mut on_read: impl FnMut(&[u8]) + Send + 'static,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        while let Ok(n) = reader.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            on_read(&chunk[..n]);
+            buffer.extend_from_slice(&chunk[..n]);
+        }
+        buffer
+    })
+}
+
+pub struct SevenZipBackend;
+
+// 7z 在没有密码或者密码错误时输出的提示
+const SEVEN_ZIP_PASSWORD_MESSAGES: [&str; 2] = ["Wrong password", "Enter password"];
+
+// stdin 关闭, 7z 不会在终端等待输入密码
+fn run_7z(
+    command: &mut Command,
+    file_path: &Path,
+    password: Option<&str>,
+    cancel: &CancelToken,
+    on_stdout: impl FnMut(&[u8]) + Send + 'static,
+) -> Result<Output, Error> {
+    let output = run_cancellable_with(command.arg(password_arg(password)), cancel, on_stdout)?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let needs_password = SEVEN_ZIP_PASSWORD_MESSAGES
+        .iter()
+        .any(|message| stdout.contains(message) || stderr.contains(message));
+
+    if needs_password && !output.status.success() {
+        return match password {
+            Some(_) => Err(Error::WrongPassword(file_path.to_path_buf())),
+            None => Err(Error::PasswordRequired(file_path.to_path_buf())),
+        };
+    }
+
+    check_output(output, file_path)
+}
+
+// 7z 的通配符只有 `*` 和 `?`, 并且按条目名在所有子目录中匹配.
+// 字符类, `{a,b}` 和带路径分隔符的模式在 7z 里含义不同, 不能交给 7z
+fn is_7z_wildcard(pattern: &str) -> bool {
+    !pattern.contains(['[', ']', '{', '}', '\\', '/'])
+}
+
+// include 对应 -ir!, exclude 对应 -xr!, 都在所有子目录中匹配
+// include 还要放过嵌套的压缩包, 和 EntryMatcher 一样
+// 7z 表示不了的模式不传, 全部解压后由 BackendRegistry 按 globset 删除
+fn filter_args(filter: &EntryFilter) -> Vec<String> {
+    let mut args = Vec::new();
+    if !filter.include.is_empty() && filter.include.iter().all(|pattern| is_7z_wildcard(pattern)) {
+        args.extend(
+            filter
+                .include
+                .iter()
+                .map(|pattern| format!("-ir!{}", pattern)),
+        );
+        // 和 EntryMatcher 一样, 单独压缩的 gz/bz2/xz/zst 文件不放过
+        args.extend(detect::ARCHIVE_EXTENSIONS.iter().map(|ext| match *ext {
+            "gz" | "bz2" | "xz" | "zst" => format!("-ir!*.tar.{}", ext),
+            ext => format!("-ir!*.{}", ext),
+        }));
+    }
+    args.extend(
+        filter
+            .exclude
+            .iter()
+            .filter(|pattern| is_7z_wildcard(pattern))
+            .map(|pattern| format!("-xr!{}", pattern)),
+    );
+
+    args
+}
+
+// 没有密码时显式传一个空密码, 避免 7z 提示输入
+fn password_arg(password: Option<&str>) -> String {
+    format!("-p{}", password.unwrap_or_default())
+}
+
+// `-bsp1` 的进度行形如 ` 42% 13 - dir/file.txt`, 返回百分比和已经处理的文件数
+fn parse_7z_progress(line: &str) -> Option<(u64, usize)> {
+    let (percent, rest) = line.trim().split_once('%')?;
+    let percent = percent.trim().parse().ok()?;
+    let files = rest
+        .split_whitespace()
+        .next()
+        .and_then(|files| files.parse().ok())
+        .unwrap_or(0);
+
+    Some((percent, files))
+}
+
+// 7z 用退格和回车覆盖同一行来刷新进度, 按这两个字符切分, 跨块的半行留到下一次
+fn progress_parser(progress: ProgressSink, total_bytes: u64) -> impl FnMut(&[u8]) + Send {
+    let mut pending = String::new();
+
+    move |chunk| {
+        pending.push_str(&String::from_utf8_lossy(chunk));
+
+        let Some(end) = pending.rfind(['\u{8}', '\r', '\n']) else {
+            return;
+        };
+        for line in pending[..end].split(['\u{8}', '\r', '\n']) {
+            if let Some((percent, files)) = parse_7z_progress(line) {
+                // 总大小是压缩包声明的, 可能很大, 用 u128 计算避免溢出
+                let bytes = total_bytes as u128 * percent.min(100) as u128 / 100;
+                progress.set(bytes as u64, files);
+            }
+        }
+        pending.drain(..=end);
+    }
+}
+
+// 解析 `7z l -slt` 的输出, 每个条目是一段 `Key = Value` 的文本块
+fn parse_7z_slt(stdout: &str) -> Vec<ArchiveEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<ArchiveEntry> = None;
+
+    let body = stdout
+        .split_once("----------")
+        .map(|(_, body)| body)
+        .unwrap_or_default();
+
+    for line in body.lines() {
+        let Some((key, value)) = line.split_once(" = ") else {
+            continue;
+        };
+
+        match key {
+            "Path" => {
+                entries.extend(current.take());
+                current = Some(ArchiveEntry {
+                    path: PathBuf::from(value),
+                    ..Default::default()
+                });
+            }
+            "Size" => {
+                if let Some(entry) = current.as_mut() {
+                    entry.size = value.parse().unwrap_or(0);
+                }
+            }
+            "Packed Size" => {
+                if let Some(entry) = current.as_mut() {
+                    entry.compressed_size = value.parse().ok();
+                }
+            }
+            // 可能带有小数部分的秒, 只保留到秒
+            "Modified" => {
+                if let Some(entry) = current.as_mut() {
+                    entry.modified = value.get(..19).map(String::from);
+                }
+            }
+            "Folder" => {
+                if let Some(entry) = current.as_mut() {
+                    entry.is_dir = value == "+";
+                }
+            }
+            "Encrypted" => {
+                if let Some(entry) = current.as_mut() {
+                    entry.encrypted = value == "+";
+                }
+            }
+            "Symbolic Link" if !value.is_empty() => {
+                if let Some(entry) = current.as_mut() {
+                    entry.link = Some(PathBuf::from(value));
+                }
+            }
+            _ => {}
+        }
+    }
+    entries.extend(current);
+
+    entries
+}
+
+// 7z 解压 `../a.txt` 和 `/a.txt` 这样的条目时会去掉这些部分, 写到 `a.txt`
+fn sanitized_7z_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .skip_while(|name| {
+            let name = name.as_encoded_bytes();
+            name.len() == 2 && name[0].is_ascii_alphabetic() && name[1] == b':'
+        })
+        .collect()
+}
+
+// 删除被拒绝的条目解压出来的文件, 和正常条目重名的不删, 顺便删掉因此变空的目录
+fn remove_rejected_outputs(dest: &Path, rejected: &[PathBuf], safe: &[PathBuf]) {
+    for entry in rejected {
+        let sanitized = sanitized_7z_path(entry);
+        if sanitized.as_os_str().is_empty() || safe.contains(&sanitized) {
+            continue;
+        }
+
+        let file = dest.join(&sanitized);
+        if std::fs::remove_file(&file).is_err() {
+            continue;
+        }
+        for dir in file.ancestors().skip(1) {
+            if dir == dest || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+fn list_7z(path: &Path, password: Option<&str>) -> Result<Vec<ArchiveEntry>, Error> {
+    let output = run_7z(
+        Command::new("7z").arg("l").arg("-slt").arg(path),
+        path,
+        password,
+        &CancelToken::default(),
+        |_| {},
+    )?;
+
+    Ok(parse_7z_slt(&String::from_utf8_lossy(&output.stdout)))
+}
+
+impl ArchiveBackend for SevenZipBackend {
+    fn name(&self) -> &'static str {
+        "7z"
+    }
+
+    fn can_handle(&self, path: &Path) -> bool {
+        is_known_archive(path)
+    }
+
+    fn supports_volumes(&self) -> bool {
+        true
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+        list_7z(path, None)
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        dest: &Path,
+        opts: &ExtractOptions,
+    ) -> Result<ExtractReport, Error> {
+        // 头部加密的压缩包需要密码才能列出内容, 之前列不出来时这里带上密码再列一次.
+        // 还是列不出来就不知道里面有没有跳出输出目录的链接, 不解压
+        let entries = match &opts.listing {
+            Some(listing) => listing.clone(),
+            None => list_7z(path, opts.password.as_deref())?,
+        };
+
+        let total_bytes = entries
+            .iter()
+            .fold(0u64, |sum, entry| sum.saturating_add(entry.size));
+        let total_entries = entries.iter().filter(|entry| !entry.is_dir).count();
+        opts.progress.start(total_bytes, total_entries);
+
+        // 7z 创建符号链接时不做检查, 后面经过链接的条目会写到输出目录外面.
+        // 和 tar 后端一样, 跳出输出目录的链接不解压, 只记录为 rejected
+        let unsafe_links: Vec<PathBuf> = entries
+            .iter()
+            .filter(|entry| {
+                entry
+                    .link
+                    .as_ref()
+                    .is_some_and(|target| link_escapes(&dest.join(&entry.path), target, dest))
+            })
+            .map(|entry| entry.path.clone())
+            .collect();
+
+        let mut command = Command::new("7z");
+        command
+            .arg("x")
+            .arg("-bsp1")
+            // 临时目录是空的, 只有压缩包里的重名条目会冲突, 和其他后端一样后面的覆盖前面的
+            .arg("-aoa")
+            // 不创建符号链接, 支持这个开关的 7z 会把链接写成普通文件
+            .arg("-snl-")
+            .arg(path)
+            .arg(format!("-o{}", dest.display()))
+            .args(filter_args(&opts.filter))
+            .args(
+                unsafe_links
+                    .iter()
+                    .map(|link| format!("-x!{}", link.display())),
+            );
+
+        run_7z(
+            &mut command,
+            path,
+            opts.password.as_deref(),
+            &opts.cancel,
+            progress_parser(opts.progress.clone(), total_bytes),
+        )?;
+
+        // 7z 会去掉 `../` 和绝对路径再解压, 这些条目解压出来的文件要删掉, 只记录为 rejected
+        // 被 7z 的通配符过滤掉的条目没有解压出来, 计入 filtered
+        let (safe, rejected): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| entry.path)
+            .partition(|path| is_safe_entry_path(path) && !unsafe_links.contains(path));
+        remove_rejected_outputs(dest, &rejected, &safe);
+        let (files, filtered): (Vec<_>, Vec<_>) = safe
+            .into_iter()
+            .map(|path| dest.join(path))
+            .partition(|file| file.exists());
+
+        Ok(ExtractReport {
+            files,
+            rejected,
+            filtered: filtered.len(),
+            ..Default::default()
+        })
+    }
+}
+
+pub struct BandizipBackend;
+
+impl ArchiveBackend for BandizipBackend {
+    fn name(&self) -> &'static str {
+        "bandizip"
+    }
+
+    fn can_handle(&self, path: &Path) -> bool {
+        is_known_archive(path)
+    }
+
+    fn supports_volumes(&self) -> bool {
+        true
+    }
+
+    fn list(&self, _path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+        // TODO: 解析 Bandizip 的列表输出
+        Err(Error::SystemNotSupport)
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        dest: &Path,
+        opts: &ExtractOptions,
+    ) -> Result<ExtractReport, Error> {
+        // bz.exe 是 Bandizip 的命令行版本, 每个参数单独传, 路径和密码里的引号不会出问题
+        let mut command = Command::new("bz.exe");
+        command.arg("x").arg("-y");
+        if let Some(password) = &opts.password {
+            command.arg(format!("-p:{}", password));
+        }
+        command.arg(format!("-o:{}", dest.display())).arg(path);
+
+        let output = run_cancellable(&mut command, &opts.cancel)?;
+
+        // 加密的压缩包不给密码时 Bandizip 不会报错, 只是什么都不解压, 所以按临时目录是否为空判断
+        // 空的压缩包也会被当成需要密码, 最后以 Error::PasswordRequired 结束
+        let is_empty = std::fs::read_dir(dest)?.next().is_none();
+        match &opts.password {
+            None if is_empty && output.status.success() => {
+                return Err(Error::PasswordRequired(path.to_path_buf()))
+            }
+            Some(_) if is_empty => return Err(Error::WrongPassword(path.to_path_buf())),
+            _ => {}
+        }
+        check_output(output, path)?;
+
+        // 还不能解析 Bandizip 的列表输出, Bandizip 命令行也没有按条目过滤的参数,
+        // 只能全部解压后由 BackendRegistry 按过滤规则删除
+        Ok(ExtractReport::from_dir(dest))
+    }
+}
+
+#[cfg(test)]
+mod external_test {
+    use super::*;
+
+    #[test]
+    fn test_parse_7z_slt() {
+        let stdout = "\
+7-Zip [64] 16.02
+
+Listing archive: test.7z
+
+--
+Path = test.7z
+Type = 7z
+
+----------
+Path = dir
+Size = 0
+Folder = +
+
+Path = dir/hello.txt
+Size = 5
+Packed Size = 9
+Modified = 2021-03-04 05:06:07.1234567
+Folder = -
+Encrypted = +
+
+Path = dir/link
+Size = 0
+Folder = -
+Symbolic Link = ../../etc
+";
+
+        let entries = parse_7z_slt(stdout);
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_dir);
+        assert_eq!(entries[1].path, PathBuf::from("dir/hello.txt"));
+        assert_eq!(entries[1].size, 5);
+        assert_eq!(entries[1].compressed_size, Some(9));
+        assert_eq!(entries[1].modified.as_deref(), Some("2021-03-04 05:06:07"));
+        assert!(entries[1].encrypted);
+        assert_eq!(entries[1].link, None);
+        assert_eq!(entries[2].link, Some(PathBuf::from("../../etc")));
+    }
+
+    #[test]
+    fn test_parse_7z_progress() {
+        assert_eq!(parse_7z_progress(" 42% 13 - dir/file.txt"), Some((42, 13)));
+        assert_eq!(parse_7z_progress("  0%"), Some((0, 0)));
+        assert_eq!(parse_7z_progress("Everything is Ok"), None);
+    }
+
+    #[test]
+    fn test_filter_args() {
+        let filter = EntryFilter {
+            include: vec!["*.txt".to_string()],
+            exclude: vec!["core*".to_string()],
+        };
+        let args = filter_args(&filter);
+        assert_eq!(args[0], "-ir!*.txt");
+        assert!(args.contains(&"-ir!*.zip".to_string()));
+        assert!(args.contains(&"-ir!*.tar.gz".to_string()));
+        assert!(!args.contains(&"-ir!*.gz".to_string()));
+        assert_eq!(args.last().unwrap(), "-xr!core*");
+
+        assert!(filter_args(&EntryFilter::default()).is_empty());
+
+        // 7z 表示不了的 include 不传, 全部解压; 表示不了的 exclude 单独去掉
+        let filter = EntryFilter {
+            include: vec!["*.txt".to_string(), "*.{log,json}".to_string()],
+            exclude: vec![
+                "[ab].log".to_string(),
+                "**/tmp".to_string(),
+                "*.dmp".to_string(),
+            ],
+        };
+        assert_eq!(filter_args(&filter), vec!["-xr!*.dmp".to_string()]);
+    }
+
+    #[test]
+    fn test_remove_rejected_outputs() {
+        assert_eq!(
+            sanitized_7z_path(Path::new("../../a/b.txt")),
+            PathBuf::from("a/b.txt")
+        );
+        assert_eq!(
+            sanitized_7z_path(Path::new("/etc/x")),
+            PathBuf::from("etc/x")
+        );
+        assert_eq!(sanitized_7z_path(Path::new("C:/x")), PathBuf::from("x"));
+
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let dest = temp_dir.path();
+        std::fs::create_dir_all(dest.join("etc")).unwrap();
+        std::fs::write(dest.join("etc/passwd"), b"evil").unwrap();
+        std::fs::write(dest.join("a.txt"), b"a").unwrap();
+
+        let rejected = [PathBuf::from("/etc/passwd"), PathBuf::from("../a.txt")];
+        remove_rejected_outputs(dest, &rejected, &[PathBuf::from("a.txt")]);
+        assert!(!dest.join("etc").exists());
+        assert!(dest.join("a.txt").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_progress_parser_overflow() {
+        let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = {
+            let reported = reported.clone();
+            ProgressSink::new(move |progress| reported.lock().unwrap().push(progress.bytes))
+        };
+
+        let mut parse = progress_parser(sink, u64::MAX);
+        parse(b" 50% 1 - a.txt\r");
+        assert_eq!(*reported.lock().unwrap(), vec![u64::MAX / 2]);
+    }
+
+    #[test]
+    fn test_run_cancellable() {
+        if std::env::consts::OS == "windows" {
+            return;
+        }
+
+        let cancel = CancelToken::default();
+        let output = run_cancellable(Command::new("echo").arg("hello"), &cancel).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
+
+        cancel.cancel();
+        let start = std::time::Instant::now();
+        let result = run_cancellable(Command::new("sleep").arg("10"), &cancel);
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let result = run_cancellable(
+            &mut Command::new("zipdive-tool-not-exists"),
+            &CancelToken::default(),
+        );
+        assert!(matches!(result, Err(Error::ToolNotFound(_))));
+    }
+
+    #[test]
+    fn test_classify_output() {
+        let path = Path::new("a.7z");
+        let error = classify_output("ERROR: CRC Failed : a.txt", path);
+        assert!(matches!(
+            error,
+            Error::ArchiveError((ArchiveErrorKind::CrcMismatch, _, _))
+        ));
+
+        let error = classify_output("ERROR: a.7z\nCan not open the file as archive", path);
+        assert!(matches!(
+            error,
+            Error::ArchiveError((ArchiveErrorKind::CorruptHeader, _, _))
+        ));
+
+        let error = classify_output("write error: No space left on device", path);
+        assert!(matches!(error, Error::DiskFull));
+    }
+}
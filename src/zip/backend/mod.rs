@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use walkdir::WalkDir;
+
+use crate::error::{ArchiveErrorKind, Error};
+use crate::zip::conflict::{self, ConflictPolicy};
+use crate::zip::detect::{self, ArchiveFormat};
+use crate::zip::filter::EntryFilter;
+use crate::zip::limits::OutputLimit;
+use crate::zip::volume::Archive;
+
+mod external;
+mod native_tar;
+mod native_zip;
+mod progress;
+mod safety;
+
+pub use external::{BandizipBackend, SevenZipBackend};
+pub use native_tar::NativeTarBackend;
+pub use native_zip::NativeZipBackend;
+pub use progress::{ExtractProgress, ProgressReader, ProgressSink};
+
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    // 压缩后的大小, tar.gz 这类整体压缩的格式没有单个条目的压缩大小
+    pub compressed_size: Option<u64>,
+    // 修改时间, 格式为 `YYYY-MM-DD HH:MM:SS`
+    pub modified: Option<String>,
+    pub is_dir: bool,
+    pub encrypted: bool,
+    // 符号链接指向的路径, 不是符号链接时为 None
+    pub link: Option<PathBuf>,
+}
+
+// 克隆出来的 token 共享同一个取消标记
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// 一次解压的结果
+#[derive(Debug, Clone, Default)]
+pub struct ExtractReport {
+    // 解压出来的文件, 不包括目录
+    pub files: Vec<PathBuf>,
+    // 因为会跳出输出目录而被跳过或删除的条目
+    pub rejected: Vec<PathBuf>,
+    // 被 include/exclude 过滤掉的文件数
+    pub filtered: usize,
+    // 输出目录中已有同名文件而跳过的文件数
+    pub skipped: usize,
+}
+
+impl ExtractReport {
+    // 后端拿不到条目列表时, 把输出目录中的所有文件都当作解压出来的文件
+    pub fn from_dir(dest: &Path) -> Self {
+        Self {
+            files: WalkDir::new(dest)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    pub password: Option<String>,
+    // 后端需要定期检查, 取消后尽快返回 Error::Cancelled
+    pub cancel: CancelToken,
+    pub progress: ProgressSink,
+    pub filter: EntryFilter,
+    // 后端只解压到空的临时目录, 由 BackendRegistry 按它移到输出目录
+    pub conflict: ConflictPolicy,
+    // 取消时把已经解压出来的部分移到输出目录, 否则直接丢掉
+    pub keep_partial: bool,
+    // 进程内的后端边解压边计数, 超出时返回对应的限制错误
+    pub output_limit: OutputLimit,
+    // BackendRegistry 已经列出来的内容, 需要先列出内容的后端不用再列一次
+    pub listing: Option<Vec<ArchiveEntry>>,
+}
+
+pub trait ArchiveBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn can_handle(&self, path: &Path) -> bool;
+
+    // 能否处理分卷压缩, 分卷压缩只会交给返回 true 的后端
+    fn supports_volumes(&self) -> bool {
+        false
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<ArchiveEntry>, Error>;
+
+    fn extract(
+        &self,
+        path: &Path,
+        dest: &Path,
+        opts: &ExtractOptions,
+    ) -> Result<ExtractReport, Error>;
+}
+
+// 根据文件内容判断是不是压缩包, gz/bz2/xz/zst 只压缩单个文件, 里面是 tar 时才算
+pub fn is_archive_content(path: &Path) -> bool {
+    match detect::sniff_format(path) {
+        Some(
+            ArchiveFormat::Gzip | ArchiveFormat::Bzip2 | ArchiveFormat::Xz | ArchiveFormat::Zstd,
+        ) => native_tar::is_compressed_tar(path),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+// 后端已经按过滤规则解压, 外部工具的通配符和 globset 的规则不完全一样, 解压后再按 globset 删除一遍
+// Bandizip 不能按条目过滤, 只能靠这里
+fn filter_output(
+    dest: &Path,
+    filter: &EntryFilter,
+    report: &mut ExtractReport,
+) -> Result<(), Error> {
+    if filter.is_empty() {
+        return Ok(());
+    }
+    let matcher = filter.matcher()?;
+
+    let mut selected = Vec::new();
+    for file in std::mem::take(&mut report.files) {
+        let relative = file.strip_prefix(dest).unwrap_or(&file);
+        if matcher.is_match_extracted(relative, || is_archive_content(&file)) {
+            selected.push(file);
+            continue;
+        }
+
+        if file.exists() {
+            std::fs::remove_file(&file)?;
+        }
+        report.filtered += 1;
+    }
+    report.files = selected;
+
+    Ok(())
+}
+
+// 解压时在输出目录下创建的临时目录的前缀
+const STAGING_PREFIX: &str = ".zipdive-";
+
+// 进程崩溃或被杀掉时临时目录会留下来, 开始解压一层前删掉 dir 下所有留下来的临时目录
+pub fn remove_stale_staging(dir: &Path) {
+    let mut walker = WalkDir::new(dir).min_depth(1).into_iter();
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let is_staging = entry.file_type().is_dir()
+            && entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(STAGING_PREFIX);
+        if is_staging {
+            walker.skip_current_dir();
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+// 按注册顺序选择第一个能处理该文件的后端, 进程内的后端应该先注册
+pub struct BackendRegistry {
+    backends: Vec<Box<dyn ArchiveBackend>>,
+    // 同一个压缩包只列一次, 空间检查, 限制检查和解压共用同一份列表
+    listings: Mutex<HashMap<PathBuf, Result<Vec<ArchiveEntry>, Error>>>,
+}
+
+impl BackendRegistry {
+    pub fn new() -> Self {
+        Self {
+            backends: Vec::new(),
+            listings: Mutex::default(),
+        }
+    }
+
+    pub fn register(&mut self, backend: impl ArchiveBackend + 'static) {
+        self.backends.push(Box::new(backend));
+    }
+
+    pub fn find(&self, archive: &Archive) -> Option<&dyn ArchiveBackend> {
+        self.backends
+            .iter()
+            .filter(|backend| !archive.is_multi_volume() || backend.supports_volumes())
+            .find(|backend| backend.can_handle(&archive.path))
+            .map(|backend| backend.as_ref())
+    }
+
+    pub fn list(&self, archive: &Archive) -> Result<Vec<ArchiveEntry>, Error> {
+        if let Some(listing) = self.cached_listing(archive) {
+            return listing;
+        }
+
+        let listing = self
+            .find(archive)
+            .ok_or(Error::SystemNotSupport)
+            .and_then(|backend| backend.list(&archive.path));
+        if let Ok(mut listings) = self.listings.lock() {
+            listings.insert(archive.path.clone(), listing.clone());
+        }
+
+        listing
+    }
+
+    pub(crate) fn cached_listing(
+        &self,
+        archive: &Archive,
+    ) -> Option<Result<Vec<ArchiveEntry>, Error>> {
+        self.listings.lock().ok()?.get(&archive.path).cloned()
+    }
+
+    // 压缩包解压完就用不到列表了, 不删掉的话整个任务的列表都会留在内存里
+    pub fn forget(&self, archive: &Archive) {
+        if let Ok(mut listings) = self.listings.lock() {
+            listings.remove(&archive.path);
+        }
+    }
+
+    pub fn extract(
+        &self,
+        archive: &Archive,
+        dest: &Path,
+        opts: &ExtractOptions,
+    ) -> Result<ExtractReport, Error> {
+        if !archive.missing_volumes.is_empty() {
+            let missing: Vec<_> = archive
+                .missing_volumes
+                .iter()
+                .map(|volume| volume.display().to_string())
+                .collect();
+            return Err(Error::ArchiveError((
+                ArchiveErrorKind::MissingVolume,
+                archive.path.clone(),
+                missing.join(", "),
+            )));
+        }
+        opts.cancel.check()?;
+        opts.output_limit.reset();
+
+        let backend = self.find(archive).ok_or(Error::SystemNotSupport)?;
+        let opts = &ExtractOptions {
+            listing: self
+                .cached_listing(archive)
+                .and_then(|listing| listing.ok()),
+            ..opts.clone()
+        };
+
+        // 先解压到输出目录下的临时目录, 所有后端遇到同名文件时的处理都一样
+        let staging = dest.join(format!(
+            "{}{}",
+            STAGING_PREFIX,
+            archive
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        ));
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        std::fs::create_dir_all(&staging)?;
+
+        // 外部工具写出来的内容没法提前检查, 所有后端都在解压后再检查一遍
+        let mut report = ExtractReport::default();
+        let result = backend
+            .extract(&archive.path, &staging, opts)
+            .and_then(|extracted| {
+                report = extracted;
+                safety::verify_output(&staging, &mut report)?;
+                filter_output(&staging, &opts.filter, &mut report)?;
+                // 外部工具不会按 output_limit 计数, 声明的大小也可能是假的
+                opts.output_limit.check_files(&report.files)
+            });
+
+        // 换密码重试前, 超出限制和取消时丢掉这次解压出来的文件, 其他错误保留已经解压出来的部分
+        let result = match result {
+            Err(e @ (Error::PasswordRequired(_) | Error::WrongPassword(_))) => Err(e),
+            Err(e) if e.is_limit_exceeded() => Err(e),
+            Err(Error::Cancelled) if !opts.keep_partial => Err(Error::Cancelled),
+            Err(e) => {
+                let _ = safety::verify_output(&staging, &mut report)
+                    .and_then(|_| filter_output(&staging, &opts.filter, &mut report))
+                    .and_then(|_| conflict::move_into(&staging, dest, opts.conflict, &mut report));
+                Err(e)
+            }
+            Ok(()) => conflict::move_into(&staging, dest, opts.conflict, &mut report),
+        };
+        // 清理失败不能盖掉解压的结果, 成功时文件已经移出去了, 留下的目录下次开始解压时再删
+        let _ = std::fs::remove_dir_all(&staging);
+
+        result.map(|_| report)
+    }
+}
+
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(NativeZipBackend);
+        registry.register(NativeTarBackend);
+
+        match std::env::consts::OS {
+            "windows" => registry.register(BandizipBackend),
+            "linux" | "macos" => registry.register(SevenZipBackend),
+            _ => {}
+        }
+
+        registry
+    }
+}
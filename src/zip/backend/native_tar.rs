@@ -0,0 +1,214 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use xz2::read::XzDecoder;
+
+use crate::error::Error;
+use crate::zip::detect::{self, ArchiveFormat};
+use crate::zip::utils::format_unix_time;
+
+use super::safety::{is_safe_entry_path, link_escapes};
+use super::{
+    ArchiveBackend, ArchiveEntry, CancelToken, ExtractOptions, ExtractReport, ProgressReader,
+    ProgressSink,
+};
+
+pub struct NativeTarBackend;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TarCompression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+fn tar_compression(file: &Path) -> Option<TarCompression> {
+    let name = file.file_name()?.to_str()?.to_ascii_lowercase();
+
+    if name.ends_with(".tar") {
+        Some(TarCompression::None)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(TarCompression::Gzip)
+    } else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") || name.ends_with(".tbz") {
+        Some(TarCompression::Bzip2)
+    } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+        Some(TarCompression::Xz)
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        Some(TarCompression::Zstd)
+    } else {
+        None
+    }
+}
+
+// 后缀名不是 tar 的时候, 解压开头一段看看里面是不是 tar
+fn sniff_tar_compression(file: &Path) -> Option<TarCompression> {
+    let compression = match detect::sniff_format(file)? {
+        ArchiveFormat::Tar => return Some(TarCompression::None),
+        ArchiveFormat::Gzip => TarCompression::Gzip,
+        ArchiveFormat::Bzip2 => TarCompression::Bzip2,
+        ArchiveFormat::Xz => TarCompression::Xz,
+        ArchiveFormat::Zstd => TarCompression::Zstd,
+        _ => return None,
+    };
+
+    let mut header = Vec::with_capacity(512);
+    open_reader(
+        file,
+        compression,
+        ProgressSink::default(),
+        CancelToken::default(),
+    )
+    .ok()?
+    .take(512)
+    .read_to_end(&mut header)
+    .ok()?;
+
+    detect::is_tar_header(&header).then_some(compression)
+}
+
+pub fn is_compressed_tar(file: &Path) -> bool {
+    sniff_tar_compression(file).is_some_and(|compression| compression != TarCompression::None)
+}
+
+// 进度按读取的压缩数据计算, 解压后的总大小要读完整个流才知道
+fn open_reader(
+    file_path: &Path,
+    compression: TarCompression,
+    progress: ProgressSink,
+    cancel: CancelToken,
+) -> Result<Box<dyn Read>, Error> {
+    let file = BufReader::new(ProgressReader::new(
+        File::open(file_path)?,
+        progress,
+        cancel,
+    ));
+
+    let reader: Box<dyn Read> = match compression {
+        TarCompression::None => Box::new(file),
+        TarCompression::Gzip => Box::new(GzDecoder::new(file)),
+        TarCompression::Bzip2 => Box::new(BzDecoder::new(file)),
+        TarCompression::Xz => Box::new(XzDecoder::new(file)),
+        TarCompression::Zstd => Box::new(zstd::Decoder::with_buffer(file)?),
+    };
+
+    Ok(reader)
+}
+
+fn open_archive(
+    file_path: &Path,
+    progress: ProgressSink,
+    cancel: CancelToken,
+) -> Result<tar::Archive<Box<dyn Read>>, Error> {
+    let compression = tar_compression(file_path)
+        .or_else(|| sniff_tar_compression(file_path))
+        .ok_or(Error::SystemNotSupport)?;
+
+    Ok(tar::Archive::new(open_reader(
+        file_path,
+        compression,
+        progress,
+        cancel,
+    )?))
+}
+
+fn tar_error(e: std::io::Error, file_path: &Path) -> Error {
+    Error::from_archive_io(e, file_path)
+}
+
+impl ArchiveBackend for NativeTarBackend {
+    fn name(&self) -> &'static str {
+        "tar"
+    }
+
+    fn can_handle(&self, path: &Path) -> bool {
+        tar_compression(path).is_some() || sniff_tar_compression(path).is_some()
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+        let mut archive = open_archive(path, ProgressSink::default(), CancelToken::default())?;
+        let entries = archive.entries().map_err(|e| tar_error(e, path))?;
+
+        entries
+            .map(|entry| {
+                let entry = entry.map_err(|e| tar_error(e, path))?;
+                let header = entry.header();
+                let link = match header.entry_type().is_symlink() {
+                    true => entry.link_name().map_err(|e| tar_error(e, path))?,
+                    false => None,
+                };
+
+                Ok(ArchiveEntry {
+                    path: entry.path().map_err(|e| tar_error(e, path))?.into_owned(),
+                    size: header.size().unwrap_or(0),
+                    modified: header.mtime().ok().map(format_unix_time),
+                    is_dir: header.entry_type().is_dir(),
+                    link: link.map(|link| link.into_owned()),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        dest: &Path,
+        opts: &ExtractOptions,
+    ) -> Result<ExtractReport, Error> {
+        opts.progress.start(std::fs::metadata(path)?.len(), 0);
+        // 不保留权限时 tar 会去掉 setuid, setgid 和 sticky 位
+        let mut archive = open_archive(path, opts.progress.clone(), opts.cancel.clone())?;
+        let mut report = ExtractReport::default();
+        let matcher = opts.filter.matcher()?;
+
+        let entries = archive.entries().map_err(|e| tar_error(e, path))?;
+        for entry in entries {
+            opts.cancel.check()?;
+
+            let mut entry = entry.map_err(|e| tar_error(e, path))?;
+
+            let entry_type = entry.header().entry_type();
+            let name = entry.path().map_err(|e| tar_error(e, path))?.into_owned();
+            let entry_path = dest.join(&name);
+
+            // 过滤掉的条目直接跳过, tar 流会在读下一个条目时跳过它的数据
+            if !matcher.is_match(&name) {
+                if !entry_type.is_dir() {
+                    report.filtered += 1;
+                }
+                continue;
+            }
+
+            // 符号链接相对自己所在的目录, 硬链接相对压缩包的根目录
+            let link_name = entry.link_name().map_err(|e| tar_error(e, path))?;
+            let unsafe_link = match link_name {
+                Some(target) if entry_type.is_symlink() => link_escapes(&entry_path, &target, dest),
+                Some(target) if entry_type.is_hard_link() => !is_safe_entry_path(&target),
+                _ => false,
+            };
+            if !is_safe_entry_path(&name) || unsafe_link {
+                report.rejected.push(name);
+                continue;
+            }
+
+            // tar 条目写出的字节数就是头里的大小, 写之前就能检查
+            if entry_type.is_file() {
+                opts.output_limit.add(entry.size())?;
+            }
+
+            // unpack_in 会跳过包含 `..` 的条目
+            let unpacked = entry.unpack_in(dest).map_err(|e| tar_error(e, path))?;
+            if unpacked && entry_type.is_file() {
+                report.files.push(entry_path);
+            }
+            opts.progress.add_entry();
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ::zip::{result::ZipError as ZipCrateError, CompressionMethod, ZipArchive};
+
+use crate::error::{ArchiveErrorKind, Error};
+use crate::zip::detect::{self, ArchiveFormat};
+use crate::zip::limits::LimitReader;
+
+use super::safety::is_safe_entry_path;
+use super::{ArchiveBackend, ArchiveEntry, ExtractOptions, ExtractReport, ProgressReader};
+
+pub struct NativeZipBackend;
+
+fn open_archive(file_path: &Path) -> Result<ZipArchive<File>, Error> {
+    let file = File::open(file_path)?;
+    ZipArchive::new(file).map_err(|e| zip_error(e, file_path))
+}
+
+fn zip_error(e: ZipCrateError, file_path: &Path) -> Error {
+    let kind = match e {
+        ZipCrateError::UnsupportedArchive(ZipCrateError::PASSWORD_REQUIRED) => {
+            return Error::PasswordRequired(file_path.to_path_buf())
+        }
+        ZipCrateError::Io(e) => return Error::from_archive_io(e, file_path),
+        ZipCrateError::InvalidArchive(_) => ArchiveErrorKind::CorruptHeader,
+        ZipCrateError::UnsupportedArchive(_) => ArchiveErrorKind::UnsupportedMethod,
+        ZipCrateError::FileNotFound => ArchiveErrorKind::Other,
+    };
+
+    Error::ArchiveError((kind, file_path.to_path_buf(), e.to_string()))
+}
+
+// 所有条目的压缩方式都是进程内支持的 (stored/deflate/bzip2/zstd) 时才返回 true,
+// 否则交给外部工具处理
+fn can_extract(file_path: &Path) -> bool {
+    let Ok(mut archive) = open_archive(file_path) else {
+        return false;
+    };
+
+    (0..archive.len()).all(|index| {
+        archive.by_index_raw(index).is_ok_and(|entry| {
+            matches!(
+                entry.compression(),
+                CompressionMethod::Stored
+                    | CompressionMethod::Deflated
+                    | CompressionMethod::Bzip2
+                    | CompressionMethod::Zstd
+            )
+        })
+    })
+}
+
+impl ArchiveBackend for NativeZipBackend {
+    fn name(&self) -> &'static str {
+        "zip"
+    }
+
+    fn can_handle(&self, path: &Path) -> bool {
+        detect::detect_format(path) == Some(ArchiveFormat::Zip) && can_extract(path)
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+        let mut archive = open_archive(path)?;
+
+        (0..archive.len())
+            .map(|index| {
+                // zip 没有直接暴露加密标记, 不带密码打开加密的条目会要求密码
+                let encrypted = matches!(
+                    archive.by_index(index).err(),
+                    Some(ZipCrateError::UnsupportedArchive(
+                        ZipCrateError::PASSWORD_REQUIRED
+                    ))
+                );
+
+                let entry = archive
+                    .by_index_raw(index)
+                    .map_err(|e| zip_error(e, path))?;
+                let modified = entry.last_modified();
+
+                Ok(ArchiveEntry {
+                    path: entry.mangled_name(),
+                    size: entry.size(),
+                    compressed_size: Some(entry.compressed_size()),
+                    modified: Some(format!(
+                        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                        modified.year(),
+                        modified.month(),
+                        modified.day(),
+                        modified.hour(),
+                        modified.minute(),
+                        modified.second()
+                    )),
+                    is_dir: entry.is_dir(),
+                    encrypted,
+                    // 解压时符号链接按普通文件写出
+                    link: None,
+                })
+            })
+            .collect()
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        dest: &Path,
+        opts: &ExtractOptions,
+    ) -> Result<ExtractReport, Error> {
+        let mut archive = open_archive(path)?;
+        let mut report = ExtractReport::default();
+        let matcher = opts.filter.matcher()?;
+
+        // 先按原始条目名过滤, 过滤掉的加密条目也不需要密码
+        let mut selected = Vec::new();
+        let mut total_bytes = 0;
+        for index in 0..archive.len() {
+            let entry = archive
+                .by_index_raw(index)
+                .map_err(|e| zip_error(e, path))?;
+
+            if matcher.is_match(Path::new(entry.name())) {
+                total_bytes += entry.size();
+                selected.push(index);
+            } else if !entry.is_dir() {
+                report.filtered += 1;
+            }
+        }
+        opts.progress.start(total_bytes, selected.len());
+
+        for index in selected {
+            opts.cancel.check()?;
+
+            // 错误的密码有一定概率通过校验字节的检查, 之后解压数据时才会出错
+            let encrypted = opts.password.is_some()
+                && matches!(
+                    archive.by_index(index).err(),
+                    Some(ZipCrateError::UnsupportedArchive(
+                        ZipCrateError::PASSWORD_REQUIRED
+                    ))
+                );
+
+            let mut entry = match &opts.password {
+                Some(password) => archive
+                    .by_index_decrypt(index, password.as_bytes())
+                    .map_err(|e| zip_error(e, path))?
+                    .map_err(|_| Error::WrongPassword(path.to_path_buf()))?,
+                None => archive.by_index(index).map_err(|e| zip_error(e, path))?,
+            };
+
+            // 跳过不安全的路径, 例如 `../`, 绝对路径或者盘符
+            let entry_path = match entry.enclosed_name() {
+                Some(name) if is_safe_entry_path(Path::new(entry.name())) => dest.join(name),
+                _ => {
+                    report.rejected.push(PathBuf::from(entry.name()));
+                    continue;
+                }
+            };
+
+            if entry.is_dir() {
+                fs::create_dir_all(&entry_path)?;
+                opts.progress.add_entry();
+                continue;
+            }
+
+            if let Some(parent) = entry_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut output_file = File::create(&entry_path)?;
+            // 按实际解压出来的字节数计数, 不相信条目头里声明的大小
+            let reader =
+                ProgressReader::new(&mut entry, opts.progress.clone(), opts.cancel.clone());
+            io::copy(
+                &mut LimitReader::new(reader, opts.output_limit.clone()),
+                &mut output_file,
+            )
+            .map_err(|e| match Error::from_archive_io(e, path) {
+                Error::ArchiveError(_) if encrypted => Error::WrongPassword(path.to_path_buf()),
+                e => e,
+            })?;
+
+            #[cfg(unix)]
+            if let Some(mode) = entry.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+
+                // 不信任压缩包里的 setuid, setgid 和 sticky 位
+                fs::set_permissions(&entry_path, fs::Permissions::from_mode(mode & 0o777))?;
+            }
+
+            report.files.push(entry_path);
+            opts.progress.add_entry();
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::CancelToken;
+
+// 两次上报之间的最小间隔, 避免进度事件刷屏
+const REPORT_INTERVAL: Duration = Duration::from_millis(200);
+
+// 单个压缩包的解压进度, total 为 0 表示不知道总量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtractProgress {
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub entries: usize,
+    pub total_entries: usize,
+}
+
+impl ExtractProgress {
+    pub fn fraction(&self) -> Option<f32> {
+        (self.total_bytes > 0).then(|| (self.bytes as f32 / self.total_bytes as f32).min(1.0))
+    }
+}
+
+struct SinkState {
+    progress: ExtractProgress,
+    last_report: Option<Instant>,
+}
+
+// 后端通过它上报进度, 克隆出来的 sink 共享同一份进度
+#[derive(Clone)]
+pub struct ProgressSink {
+    callback: Option<Arc<dyn Fn(ExtractProgress) + Send + Sync>>,
+    state: Arc<Mutex<SinkState>>,
+}
+
+impl Default for ProgressSink {
+    fn default() -> Self {
+        Self {
+            callback: None,
+            state: Arc::new(Mutex::new(SinkState {
+                progress: ExtractProgress::default(),
+                last_report: None,
+            })),
+        }
+    }
+}
+
+impl fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProgressSink").finish_non_exhaustive()
+    }
+}
+
+impl ProgressSink {
+    pub fn new(callback: impl Fn(ExtractProgress) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Some(Arc::new(callback)),
+            ..Default::default()
+        }
+    }
+
+    // 开始解压时设置总量, 换密码重试时进度也从头开始
+    pub fn start(&self, total_bytes: u64, total_entries: usize) {
+        self.update(|progress| {
+            *progress = ExtractProgress {
+                total_bytes,
+                total_entries,
+                ..Default::default()
+            }
+        });
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.update(|progress| progress.bytes += bytes);
+    }
+
+    pub fn add_entry(&self) {
+        self.update(|progress| progress.entries += 1);
+    }
+
+    // 外部工具直接给出已经处理的字节数和条目数
+    pub fn set(&self, bytes: u64, entries: usize) {
+        self.update(|progress| {
+            progress.bytes = bytes;
+            progress.entries = entries;
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ExtractProgress)) {
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        f(&mut state.progress);
+
+        let now = Instant::now();
+        if state
+            .last_report
+            .is_some_and(|last| now.duration_since(last) < REPORT_INTERVAL)
+        {
+            return;
+        }
+        state.last_report = Some(now);
+
+        callback(state.progress);
+    }
+}
+
+// 读取时把读到的字节数计入进度, 每次读取前检查是否取消, 单个很大的条目也能及时停下
+// 取消时返回包着 Error::Cancelled 的 io::Error
+pub struct ProgressReader<R> {
+    inner: R,
+    sink: ProgressSink,
+    cancel: CancelToken,
+}
+
+impl<R> ProgressReader<R> {
+    pub fn new(inner: R, sink: ProgressSink, cancel: CancelToken) -> Self {
+        Self {
+            inner,
+            sink,
+            cancel,
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.cancel.check().map_err(io::Error::other)?;
+        let n = self.inner.read(buf)?;
+        self.sink.add_bytes(n as u64);
+
+        Ok(n)
+    }
+}
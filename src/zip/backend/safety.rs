@@ -0,0 +1,308 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::error::Error;
+
+use super::ExtractReport;
+
+// 条目路径不能是绝对路径, 不能带盘符, 也不能通过 `..` 跳出输出目录
+pub fn is_safe_entry_path(path: &Path) -> bool {
+    let has_drive_letter = path.to_str().is_some_and(|s| {
+        s.len() >= 2 && s.as_bytes()[1] == b':' && s.as_bytes()[0].is_ascii_alphabetic()
+    });
+
+    !has_drive_letter
+        && path.components().all(|component| {
+            !matches!(
+                component,
+                Component::Prefix(_) | Component::RootDir | Component::ParentDir
+            )
+        })
+}
+
+// 只按路径计算, 不访问文件系统, 链接指向的文件可能还不存在
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+// 链接 link 指向 target 时, 是否会跳出 dest
+pub fn link_escapes(link: &Path, target: &Path, dest: &Path) -> bool {
+    let resolved = match link.parent() {
+        Some(parent) if target.is_relative() => parent.join(target),
+        _ => target.to_path_buf(),
+    };
+
+    !normalize(&resolved).starts_with(normalize(dest))
+}
+
+// 解压完成后检查输出目录, 删除经过链接写到输出目录之外的文件和指向输出目录之外的符号链接
+pub fn verify_output(dest: &Path, report: &mut ExtractReport) -> Result<(), Error> {
+    let canonical_dest = fs::canonicalize(dest)?;
+
+    // 要在删除链接之前检查, 链接删掉后就找不到写到外面的文件了
+    let (files, escaped): (Vec<_>, Vec<_>) = std::mem::take(&mut report.files)
+        .into_iter()
+        .partition(|file| match file.parent().map(fs::canonicalize) {
+            Some(Ok(parent)) => parent.starts_with(&canonical_dest),
+            _ => true,
+        });
+    report.files = files;
+    for file in escaped {
+        fs::remove_file(&file)?;
+        let relative = file.strip_prefix(dest).unwrap_or(&file);
+        report.rejected.push(relative.to_path_buf());
+    }
+
+    for entry in WalkDir::new(&canonical_dest)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if !entry.path_is_symlink() {
+            continue;
+        }
+
+        let link = entry.path();
+        let escapes = match fs::read_link(link) {
+            Ok(target) => link_escapes(link, &target, &canonical_dest),
+            Err(_) => true,
+        };
+        if !escapes {
+            continue;
+        }
+
+        fs::remove_file(link)?;
+
+        let relative = link.strip_prefix(&canonical_dest).unwrap_or(link);
+        report
+            .files
+            .retain(|file| file.strip_prefix(dest).ok() != Some(relative));
+        report.rejected.push(relative.to_path_buf());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod safety_test {
+    use super::*;
+    use crate::zip::backend::{BackendRegistry, ExtractOptions};
+    use crate::zip::volume::Archive;
+
+    // dest 旁边放一个已有的文件, 检查解压后它没有被改动, 也没有多出别的文件
+    fn assert_nothing_outside(root: &Path) {
+        let mut names: Vec<_> = fs::read_dir(root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["archive", "dest", "outside.txt"]);
+        assert_eq!(
+            fs::read_to_string(root.join("outside.txt")).unwrap(),
+            "original"
+        );
+    }
+
+    fn extract(root: &Path, archive: &Path) -> ExtractReport {
+        fs::write(root.join("outside.txt"), "original").unwrap();
+        fs::create_dir_all(root.join("dest")).unwrap();
+
+        BackendRegistry::default()
+            .extract(
+                &Archive::single(archive.to_path_buf()),
+                &root.join("dest"),
+                &ExtractOptions::default(),
+            )
+            .unwrap()
+    }
+
+    // tar::Builder 会拒绝 `..`, 直接写头部
+    fn append_raw(
+        builder: &mut tar::Builder<fs::File>,
+        name: &str,
+        entry_type: tar::EntryType,
+        link: &str,
+        data: &[u8],
+    ) {
+        let mut header = tar::Header::new_gnu();
+        let gnu = header.as_gnu_mut().unwrap();
+        gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+        gnu.linkname[..link.len()].copy_from_slice(link.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    #[test]
+    fn test_malicious_tar() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let archive = root.join("archive").join("evil.tar");
+        fs::create_dir_all(archive.parent().unwrap()).unwrap();
+
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        let regular = tar::EntryType::Regular;
+        append_raw(&mut builder, "ok.txt", regular, "", b"ok");
+        append_raw(&mut builder, "../evil.txt", regular, "", b"evil");
+        append_raw(&mut builder, "a/../../evil.txt", regular, "", b"evil");
+        append_raw(&mut builder, "link", tar::EntryType::Symlink, "../", b"");
+        append_raw(&mut builder, "link/evil.txt", regular, "", b"evil");
+        append_raw(&mut builder, "abs", tar::EntryType::Symlink, "/", b"");
+        append_raw(
+            &mut builder,
+            "hard",
+            tar::EntryType::Link,
+            "../outside.txt",
+            b"",
+        );
+        builder.into_inner().unwrap();
+
+        let report = extract(root, &archive);
+
+        assert_nothing_outside(root);
+        assert!(report.files.contains(&root.join("dest").join("ok.txt")));
+        for name in ["../evil.txt", "a/../../evil.txt", "link", "abs", "hard"] {
+            assert!(report.rejected.contains(&PathBuf::from(name)), "{}", name);
+        }
+        assert!(!root.join("dest").join("link").is_symlink());
+
+        // 列出内容时带上链接指向的路径, 7z 后端靠它提前排除链接
+        let entries = BackendRegistry::default()
+            .list(&Archive::single(archive.clone()))
+            .unwrap();
+        let link = entries.iter().find(|entry| entry.path == Path::new("link"));
+        assert_eq!(link.unwrap().link, Some(PathBuf::from("../")));
+
+        temp_dir.close().unwrap();
+    }
+
+    // 外部工具不一定会检查链接, 经过链接写到外面的文件也要删掉
+    #[cfg(unix)]
+    #[test]
+    fn test_written_through_link() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let dest = root.join("dest");
+        fs::create_dir_all(root.join("outside")).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        std::os::unix::fs::symlink("../outside", dest.join("link")).unwrap();
+        fs::write(dest.join("link").join("evil.txt"), "evil").unwrap();
+        fs::write(dest.join("ok.txt"), "ok").unwrap();
+
+        let mut report = ExtractReport {
+            files: vec![dest.join("link").join("evil.txt"), dest.join("ok.txt")],
+            ..Default::default()
+        };
+        verify_output(&dest, &mut report).unwrap();
+
+        assert!(!root.join("outside").join("evil.txt").exists());
+        assert!(!dest.join("link").exists());
+        assert_eq!(report.files, vec![dest.join("ok.txt")]);
+        assert!(report.rejected.contains(&PathBuf::from("link/evil.txt")));
+        assert!(report.rejected.contains(&PathBuf::from("link")));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_mode_bits() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let archive = root.join("archive").join("setuid.tar");
+        fs::create_dir_all(archive.parent().unwrap()).unwrap();
+
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_path("run.sh").unwrap();
+        header.set_size(2);
+        header.set_mode(0o7755);
+        header.set_cksum();
+        builder.append(&header, &b"ok"[..]).unwrap();
+        builder.into_inner().unwrap();
+
+        extract(root, &archive);
+
+        let mode = fs::metadata(root.join("dest").join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o755);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_malicious_zip() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let archive = root.join("archive").join("evil.zip");
+        fs::create_dir_all(archive.parent().unwrap()).unwrap();
+
+        let mut zip = ::zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        for name in ["ok.txt", "../evil.txt", "a/../../evil.txt"] {
+            zip.start_file(name, Default::default()).unwrap();
+            std::io::Write::write_all(&mut zip, b"evil").unwrap();
+        }
+        // zip 中的符号链接按普通文件写出, 不会变成真的链接
+        zip.add_symlink("link", "../", Default::default()).unwrap();
+        zip.finish().unwrap();
+
+        let report = extract(root, &archive);
+
+        assert_nothing_outside(root);
+        assert!(report.files.contains(&root.join("dest").join("ok.txt")));
+        for name in ["../evil.txt", "a/../../evil.txt"] {
+            assert!(report.rejected.contains(&PathBuf::from(name)), "{}", name);
+        }
+        assert!(!root.join("dest").join("link").is_symlink());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_is_safe_entry_path() {
+        assert!(is_safe_entry_path(Path::new("a/b.txt")));
+        assert!(is_safe_entry_path(Path::new("./a/b.txt")));
+        assert!(!is_safe_entry_path(Path::new("../b.txt")));
+        assert!(!is_safe_entry_path(Path::new("a/../../b.txt")));
+        assert!(!is_safe_entry_path(Path::new("/etc/passwd")));
+        assert!(!is_safe_entry_path(Path::new("C:/Windows/b.txt")));
+    }
+
+    #[test]
+    fn test_link_escapes() {
+        let dest = Path::new("/out");
+        assert!(!link_escapes(
+            Path::new("/out/a/link"),
+            Path::new("../b"),
+            dest
+        ));
+        assert!(link_escapes(
+            Path::new("/out/a/link"),
+            Path::new("../../b"),
+            dest
+        ));
+        assert!(link_escapes(
+            Path::new("/out/link"),
+            Path::new("/etc/passwd"),
+            dest
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::error::Error;
+
+use super::backend::ExtractReport;
+
+// 输出目录中已经有同名文件时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    #[default]
+    Overwrite,
+    Skip,
+    // 新文件改名为 `file (1).txt`
+    RenameNew,
+    // 已有的文件改名为 `file (1).txt`, 新文件用原来的名字
+    RenameExisting,
+    // 返回 Error::FileExists
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 5] = [
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::Skip,
+        ConflictPolicy::RenameNew,
+        ConflictPolicy::RenameExisting,
+        ConflictPolicy::Fail,
+    ];
+
+    // 返回新文件最终的路径, None 表示跳过
+    // 两边都是目录时 move_into 会合并, 不会调用到这里
+    pub fn resolve(self, target: &Path) -> Result<Option<PathBuf>, Error> {
+        // 不跟随符号链接, 已有的链接也算同名文件
+        let Ok(metadata) = fs::symlink_metadata(target) else {
+            return Ok(Some(target.to_path_buf()));
+        };
+
+        match self {
+            // 新文件和已有的目录同名时不删除整个目录, 当作冲突报错
+            ConflictPolicy::Overwrite if metadata.is_dir() => {
+                Err(Error::FileExists(target.to_path_buf()))
+            }
+            ConflictPolicy::Overwrite => {
+                // 先删掉再写, 避免顺着已有的符号链接写到别处
+                fs::remove_file(target)?;
+                Ok(Some(target.to_path_buf()))
+            }
+            ConflictPolicy::Skip => Ok(None),
+            ConflictPolicy::RenameNew => Ok(Some(unique_path(target))),
+            ConflictPolicy::RenameExisting => {
+                fs::rename(target, unique_path(target))?;
+                Ok(Some(target.to_path_buf()))
+            }
+            ConflictPolicy::Fail => Err(Error::FileExists(target.to_path_buf())),
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConflictPolicy::Overwrite => write!(f, "覆盖"),
+            ConflictPolicy::Skip => write!(f, "跳过"),
+            ConflictPolicy::RenameNew => write!(f, "重命名新文件"),
+            ConflictPolicy::RenameExisting => write!(f, "重命名旧文件"),
+            ConflictPolicy::Fail => write!(f, "报错"),
+        }
+    }
+}
+
+// `file.txt` -> `file (1).txt`, 编号从 1 开始找第一个不存在的
+fn unique_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// 把 staging 中解压出来的内容按 policy 移到 dest, report 中的路径换成移动后的路径
+// 两边都是目录时合并里面的内容, 其他情况整个移动
+pub fn move_into(
+    staging: &Path,
+    dest: &Path,
+    policy: ConflictPolicy,
+    report: &mut ExtractReport,
+) -> Result<(), Error> {
+    // 报错时不能只移动了一部分, 先检查所有的文件再开始移动
+    if policy == ConflictPolicy::Fail {
+        check_conflicts(staging, dest)?;
+    }
+
+    let mut moved = HashMap::new();
+
+    let mut walker = WalkDir::new(staging)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry.map_err(io::Error::from)?;
+        let relative = entry.path().strip_prefix(staging).unwrap_or(entry.path());
+        let target = dest.join(relative);
+
+        if entry.file_type().is_dir() {
+            if fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.is_dir()) {
+                continue;
+            }
+            walker.skip_current_dir();
+        }
+
+        if let Some(target) = policy.resolve(&target)? {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(entry.path(), &target)?;
+            moved.insert(entry.path().to_path_buf(), target);
+        }
+    }
+
+    // 文件本身或者它所在的目录被移动过, 没有移动的就是被跳过了
+    let total = report.files.len();
+    report.files = report
+        .files
+        .iter()
+        .filter_map(|file| {
+            file.ancestors().find_map(|ancestor| {
+                let target = moved.get(ancestor)?;
+                let rest = file.strip_prefix(ancestor).ok()?;
+                Some(if rest.as_os_str().is_empty() {
+                    target.clone()
+                } else {
+                    target.join(rest)
+                })
+            })
+        })
+        .collect();
+    report.skipped += total - report.files.len();
+
+    Ok(())
+}
+
+// 和 move_into 一样遍历, 有任何同名文件就返回 Error::FileExists
+fn check_conflicts(staging: &Path, dest: &Path) -> Result<(), Error> {
+    let mut walker = WalkDir::new(staging)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry.map_err(io::Error::from)?;
+        let relative = entry.path().strip_prefix(staging).unwrap_or(entry.path());
+        let target = dest.join(relative);
+
+        let Ok(metadata) = fs::symlink_metadata(&target) else {
+            // 目录整个移动过去, 里面的内容不会冲突
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+            continue;
+        };
+        if !(entry.file_type().is_dir() && metadata.is_dir()) {
+            return Err(Error::FileExists(target));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod conflict_test {
+    use super::*;
+
+    fn extract_twice(policy: ConflictPolicy) -> (assert_fs::TempDir, Result<ExtractReport, Error>) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let dest = temp_dir.path().join("dest");
+        std::fs::create_dir_all(dest.join("dir")).unwrap();
+        std::fs::write(dest.join("dir").join("a.txt"), "old").unwrap();
+
+        let staging = temp_dir.path().join("staging");
+        std::fs::create_dir_all(staging.join("dir")).unwrap();
+        std::fs::write(staging.join("dir").join("a.txt"), "new").unwrap();
+        std::fs::write(staging.join("dir").join("b.txt"), "new").unwrap();
+
+        let mut report = ExtractReport {
+            files: vec![
+                staging.join("dir").join("a.txt"),
+                staging.join("dir").join("b.txt"),
+            ],
+            ..Default::default()
+        };
+        let result = move_into(&staging, &dest, policy, &mut report).map(|_| report);
+
+        (temp_dir, result)
+    }
+
+    #[test]
+    fn test_move_into() {
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+
+        let (temp_dir, report) = extract_twice(ConflictPolicy::Overwrite);
+        let dir = temp_dir.path().join("dest").join("dir");
+        assert_eq!(
+            report.unwrap().files,
+            vec![dir.join("a.txt"), dir.join("b.txt")]
+        );
+        assert_eq!(read(dir.join("a.txt")), "new");
+
+        let (temp_dir, report) = extract_twice(ConflictPolicy::Skip);
+        let dir = temp_dir.path().join("dest").join("dir");
+        let report = report.unwrap();
+        assert_eq!(report.files, vec![dir.join("b.txt")]);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(read(dir.join("a.txt")), "old");
+
+        let (temp_dir, report) = extract_twice(ConflictPolicy::RenameNew);
+        let dir = temp_dir.path().join("dest").join("dir");
+        assert_eq!(report.unwrap().files[0], dir.join("a (1).txt"));
+        assert_eq!(read(dir.join("a.txt")), "old");
+        assert_eq!(read(dir.join("a (1).txt")), "new");
+
+        let (temp_dir, report) = extract_twice(ConflictPolicy::RenameExisting);
+        let dir = temp_dir.path().join("dest").join("dir");
+        assert_eq!(report.unwrap().files[0], dir.join("a.txt"));
+        assert_eq!(read(dir.join("a.txt")), "new");
+        assert_eq!(read(dir.join("a (1).txt")), "old");
+
+        let (_temp_dir, report) = extract_twice(ConflictPolicy::Fail);
+        assert!(matches!(report, Err(Error::FileExists(_))));
+    }
+
+    #[test]
+    fn test_overwrite_directory_with_file() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let dest = temp_dir.path().join("dest");
+        std::fs::create_dir_all(dest.join("a")).unwrap();
+        std::fs::write(dest.join("a").join("old.txt"), "old").unwrap();
+
+        let staging = temp_dir.path().join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("a"), "new").unwrap();
+
+        let mut report = ExtractReport {
+            files: vec![staging.join("a")],
+            ..Default::default()
+        };
+        let result = move_into(&staging, &dest, ConflictPolicy::Overwrite, &mut report);
+        assert!(matches!(result, Err(Error::FileExists(path)) if path == dest.join("a")));
+        assert_eq!(
+            std::fs::read_to_string(dest.join("a").join("old.txt")).unwrap(),
+            "old"
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_fail_leaves_dest_unchanged() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let dest = temp_dir.path().join("dest");
+        std::fs::create_dir_all(dest.join("dir")).unwrap();
+        std::fs::write(dest.join("dir").join("z.txt"), "old").unwrap();
+
+        // 排在前面的文件没有冲突, 也不能被移过去
+        let staging = temp_dir.path().join("staging");
+        std::fs::create_dir_all(staging.join("dir")).unwrap();
+        std::fs::create_dir_all(staging.join("new")).unwrap();
+        std::fs::write(staging.join("a.txt"), "new").unwrap();
+        std::fs::write(staging.join("dir").join("a.txt"), "new").unwrap();
+        std::fs::write(staging.join("dir").join("z.txt"), "new").unwrap();
+
+        let mut report = ExtractReport::default();
+        let result = move_into(&staging, &dest, ConflictPolicy::Fail, &mut report);
+        assert!(
+            matches!(result, Err(Error::FileExists(path)) if path == dest.join("dir").join("z.txt"))
+        );
+
+        let mut names: Vec<_> = WalkDir::new(&dest)
+            .min_depth(1)
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![dest.join("dir"), dest.join("dir").join("z.txt")]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("dir").join("z.txt")).unwrap(),
+            "old"
+        );
+
+        temp_dir.close().unwrap();
+    }
+}
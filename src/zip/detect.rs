@@ -0,0 +1,251 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::is_archive_content;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Rar,
+    SevenZip,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    Tar,
+    Cab,
+    Iso,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DetectPolicy {
+    #[default]
+    Extension,
+    MagicBytes,
+    Both,
+}
+
+impl DetectPolicy {
+    pub const ALL: [DetectPolicy; 3] = [
+        DetectPolicy::Extension,
+        DetectPolicy::MagicBytes,
+        DetectPolicy::Both,
+    ];
+}
+
+impl fmt::Display for DetectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectPolicy::Extension => write!(f, "后缀名"),
+            DetectPolicy::MagicBytes => write!(f, "文件头"),
+            DetectPolicy::Both => write!(f, "后缀名或文件头"),
+        }
+    }
+}
+
+// ISO 9660 的卷描述符从 0x8001 开始, 需要读到这里才能识别
+const SNIFF_LEN: usize = 0x8001 + 5;
+
+const TAR_MAGIC_OFFSET: usize = 257;
+
+// 这些文件本质上是 zip, 但一般不希望被当作压缩包解开
+const ZIP_CONTAINER_EXTENSIONS: [&str; 9] = [
+    "docx", "xlsx", "pptx", "odt", "ods", "odp", "jar", "apk", "epub",
+];
+
+// format_from_extension 能识别的所有后缀名
+pub const ARCHIVE_EXTENSIONS: [&str; 14] = [
+    "zip", "rar", "7z", "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "tzst", "tar", "cab", "iso",
+];
+
+pub fn format_from_extension(file: &Path) -> Option<ArchiveFormat> {
+    let ext = file.extension()?.to_str()?.to_ascii_lowercase();
+
+    let format = match ext.as_str() {
+        "zip" => ArchiveFormat::Zip,
+        "rar" => ArchiveFormat::Rar,
+        "7z" => ArchiveFormat::SevenZip,
+        "gz" | "tgz" => ArchiveFormat::Gzip,
+        "bz2" | "tbz2" => ArchiveFormat::Bzip2,
+        "xz" | "txz" => ArchiveFormat::Xz,
+        // 只有 tar 后端能解压 zstd, 单独的 .zst 不算压缩包
+        "zst" if has_tar_stem(file) => ArchiveFormat::Zstd,
+        "tzst" => ArchiveFormat::Zstd,
+        "tar" => ArchiveFormat::Tar,
+        "cab" => ArchiveFormat::Cab,
+        "iso" => ArchiveFormat::Iso,
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+// gz/bz2/xz/zst 只压缩单个文件, 后缀名表明里面是 tar 时才可能有多个条目
+pub fn is_multi_entry_extension(file: &Path) -> bool {
+    match format_from_extension(file) {
+        Some(
+            ArchiveFormat::Gzip | ArchiveFormat::Bzip2 | ArchiveFormat::Xz | ArchiveFormat::Zstd,
+        ) => {
+            has_tar_stem(file)
+                || file
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| {
+                        ["tgz", "tbz2", "txz", "tzst"].contains(&ext.to_ascii_lowercase().as_str())
+                    })
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+// `x.tar.zst` 去掉最后一个后缀名后还是 `.tar`
+fn has_tar_stem(file: &Path) -> bool {
+    file.file_stem()
+        .and_then(|stem| Path::new(stem).extension())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tar"))
+}
+
+pub fn format_from_bytes(bytes: &[u8]) -> Option<ArchiveFormat> {
+    let at = |offset: usize, magic: &[u8]| {
+        bytes
+            .get(offset..offset + magic.len())
+            .is_some_and(|window| window == magic)
+    };
+
+    if at(0, b"PK\x03\x04") || at(0, b"PK\x05\x06") || at(0, b"PK\x07\x08") {
+        Some(ArchiveFormat::Zip)
+    } else if at(0, b"Rar!\x1A\x07\x00") || at(0, b"Rar!\x1A\x07\x01\x00") {
+        Some(ArchiveFormat::Rar)
+    } else if at(0, b"7z\xBC\xAF\x27\x1C") {
+        Some(ArchiveFormat::SevenZip)
+    } else if at(0, b"\x1F\x8B") {
+        Some(ArchiveFormat::Gzip)
+    } else if at(0, b"BZh") {
+        Some(ArchiveFormat::Bzip2)
+    } else if at(0, b"\xFD7zXZ\x00") {
+        Some(ArchiveFormat::Xz)
+    } else if at(0, b"\x28\xB5\x2F\xFD") {
+        Some(ArchiveFormat::Zstd)
+    } else if at(0, b"MSCF") {
+        Some(ArchiveFormat::Cab)
+    } else if at(TAR_MAGIC_OFFSET, b"ustar") {
+        Some(ArchiveFormat::Tar)
+    } else if at(0x8001, b"CD001") {
+        Some(ArchiveFormat::Iso)
+    } else {
+        None
+    }
+}
+
+pub fn is_tar_header(bytes: &[u8]) -> bool {
+    format_from_bytes(bytes) == Some(ArchiveFormat::Tar)
+}
+
+pub fn sniff_format(file: &Path) -> Option<ArchiveFormat> {
+    let file = File::open(file).ok()?;
+
+    let mut bytes = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut bytes).ok()?;
+
+    format_from_bytes(&bytes)
+}
+
+// 先看后缀名, 识别不出来再读文件头
+pub fn detect_format(file: &Path) -> Option<ArchiveFormat> {
+    format_from_extension(file).or_else(|| sniff_format(file))
+}
+
+// gz/bz2/xz/zst 整体压缩, 列出内容要把整个压缩包解压一遍
+pub fn is_solid_stream(file: &Path) -> bool {
+    matches!(
+        detect_format(file),
+        Some(ArchiveFormat::Gzip | ArchiveFormat::Bzip2 | ArchiveFormat::Xz | ArchiveFormat::Zstd)
+    )
+}
+
+pub fn is_compressed_file(file: &Path, policy: DetectPolicy) -> bool {
+    let by_extension = || format_from_extension(file).is_some();
+    let by_magic = || {
+        let is_container = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                ZIP_CONTAINER_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+            });
+
+        // 单独压缩的 xz/zst 这类文件不一定有后端能处理, 里面是 tar 时才算压缩包
+        !is_container && is_archive_content(file)
+    };
+
+    match policy {
+        DetectPolicy::Extension => by_extension(),
+        DetectPolicy::MagicBytes => by_magic(),
+        DetectPolicy::Both => by_extension() || by_magic(),
+    }
+}
+
+#[cfg(test)]
+mod detect_test {
+    use super::*;
+
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_format_from_bytes() {
+        assert_eq!(
+            format_from_bytes(b"PK\x03\x04rest"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            format_from_bytes(b"Rar!\x1A\x07\x01\x00"),
+            Some(ArchiveFormat::Rar)
+        );
+        assert_eq!(
+            format_from_bytes(b"7z\xBC\xAF\x27\x1C"),
+            Some(ArchiveFormat::SevenZip)
+        );
+        assert_eq!(
+            format_from_bytes(b"\x28\xB5\x2F\xFD"),
+            Some(ArchiveFormat::Zstd)
+        );
+        assert_eq!(format_from_bytes(b"hello world"), None);
+
+        let mut tar_header = vec![0u8; 512];
+        tar_header[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5].copy_from_slice(b"ustar");
+        assert_eq!(format_from_bytes(&tar_header), Some(ArchiveFormat::Tar));
+    }
+
+    #[test]
+    fn test_renamed_archive() {
+        let temp_project = assert_fs::TempDir::new().unwrap();
+        let renamed = temp_project.child("archive.dat");
+        renamed.write_binary(b"7z\xBC\xAF\x27\x1C\x00\x04").unwrap();
+
+        assert!(!is_compressed_file(renamed.path(), DetectPolicy::Extension));
+        assert!(is_compressed_file(renamed.path(), DetectPolicy::MagicBytes));
+        assert!(is_compressed_file(renamed.path(), DetectPolicy::Both));
+        assert_eq!(detect_format(renamed.path()), Some(ArchiveFormat::SevenZip));
+
+        assert_eq!(format_from_extension(Path::new("data.zst")), None);
+        assert_eq!(
+            format_from_extension(Path::new("data.TAR.zst")),
+            Some(ArchiveFormat::Zstd)
+        );
+
+        let compressed = temp_project.child("data.bin");
+        compressed
+            .write_binary(b"\x28\xB5\x2F\xFD\x00\x00")
+            .unwrap();
+        assert!(!is_compressed_file(
+            compressed.path(),
+            DetectPolicy::MagicBytes
+        ));
+
+        temp_project.close().unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+use super::detect;
+
+// 按条目路径过滤要解压的内容, include 为空时解压全部, exclude 优先
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl EntryFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    // 界面里的模式用空格或逗号分隔, `{}` 里的逗号属于 `*.{log,json}` 这样的模式
+    pub fn parse_patterns(input: &str) -> Vec<String> {
+        let mut patterns = Vec::new();
+        let mut current = String::new();
+        let mut braces = 0usize;
+
+        for c in input.chars() {
+            match c {
+                '{' => braces += 1,
+                '}' => braces = braces.saturating_sub(1),
+                ',' if braces > 0 => {}
+                ' ' | ',' => {
+                    if !current.is_empty() {
+                        patterns.push(std::mem::take(&mut current));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+        if !current.is_empty() {
+            patterns.push(current);
+        }
+
+        patterns
+    }
+
+    pub fn matcher(&self) -> Result<EntryMatcher, Error> {
+        Ok(EntryMatcher {
+            include: (!self.include.is_empty())
+                .then(|| build_set(&self.include))
+                .transpose()?,
+            exclude: build_set(&self.exclude)?,
+        })
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| Error::InvalidPattern(e.to_string()))?);
+    }
+
+    builder
+        .build()
+        .map_err(|e| Error::InvalidPattern(e.to_string()))
+}
+
+// 编译好的过滤器, `*` 可以跨目录匹配, 所以 `*.log` 会匹配所有目录下的日志.
+// 嵌套的压缩包不受 include 限制, 否则下一层就找不到要解压的内容了.
+// 只压缩单个文件的 `core.gz` 这类文件里没有别的条目, 不匹配 include 时不解压
+#[derive(Debug, Clone)]
+pub struct EntryMatcher {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl EntryMatcher {
+    pub fn is_match(&self, path: &Path) -> bool {
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|set| set.is_match(path) || detect::is_multi_entry_extension(path));
+
+        included && !self.exclude.is_match(path)
+    }
+
+    // 解压出来之后再检查一遍, 只因为后缀名放过的文件, 还要 is_archive 确认内容真的是压缩包
+    pub fn is_match_extracted(&self, path: &Path, is_archive: impl FnOnce() -> bool) -> bool {
+        let included = self.include.as_ref().is_none_or(|set| {
+            set.is_match(path) || (detect::format_from_extension(path).is_some() && is_archive())
+        });
+
+        included && !self.exclude.is_match(path)
+    }
+}
+
+#[cfg(test)]
+mod filter_test {
+    use super::*;
+
+    #[test]
+    fn test_entry_filter() {
+        let filter = EntryFilter {
+            include: EntryFilter::parse_patterns("*.log, *.json"),
+            exclude: vec!["tmp/**".to_string()],
+        };
+        let matcher = filter.matcher().unwrap();
+
+        assert!(matcher.is_match(Path::new("a.log")));
+        assert!(matcher.is_match(Path::new("logs/b/c.json")));
+        assert!(!matcher.is_match(Path::new("core.dump")));
+        assert!(!matcher.is_match(Path::new("tmp/a.log")));
+        assert!(matcher.is_match(Path::new("nested/logs.tar.gz")));
+        assert!(!matcher.is_match(Path::new("tmp/logs.zip")));
+        assert!(matcher.is_match_extracted(Path::new("logs.tar.gz"), || true));
+        assert!(!matcher.is_match_extracted(Path::new("core.gz"), || false));
+        // 单独压缩的大文件在解压前就被过滤掉
+        assert!(!matcher.is_match(Path::new("core.gz")));
+        assert!(matcher.is_match(Path::new("logs.tgz")));
+        assert!(matcher.is_match(Path::new("nested/logs.tar.zst")));
+
+        let matcher = EntryFilter::default().matcher().unwrap();
+        assert!(matcher.is_match(Path::new("core.dump")));
+
+        let invalid = EntryFilter {
+            include: vec!["a[".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(invalid.matcher(), Err(Error::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn test_parse_patterns_with_braces() {
+        assert_eq!(
+            EntryFilter::parse_patterns("*.{log,json}, tmp/**"),
+            vec!["*.{log,json}".to_string(), "tmp/**".to_string()]
+        );
+
+        let filter = EntryFilter {
+            include: EntryFilter::parse_patterns("*.{log,json}"),
+            ..Default::default()
+        };
+        let matcher = filter.matcher().unwrap();
+        assert!(matcher.is_match(Path::new("a/b.json")));
+        assert!(!matcher.is_match(Path::new("core.dump")));
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Layout {
+    // 第 N 层解压到 target/N, 下一层从上一层的输出目录中查找压缩文件
+    #[default]
+    Layered,
+    // 所有层都解压到 target 中, 压缩包解压到它所在目录下的同名目录
+    Nested,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 2] = [Layout::Layered, Layout::Nested];
+
+    // 第 depth 层从哪个目录查找压缩文件, 解压到哪个目录
+    pub fn layer_paths(self, source: &Path, target: &Path, depth: usize) -> (PathBuf, PathBuf) {
+        match self {
+            Layout::Layered if depth <= 1 => (source.to_path_buf(), target.join("1")),
+            Layout::Layered => (
+                target.join(format!("{}", depth - 1)),
+                target.join(format!("{}", depth)),
+            ),
+            Layout::Nested if depth <= 1 => (source.to_path_buf(), target.to_path_buf()),
+            Layout::Nested => (target.to_path_buf(), target.to_path_buf()),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layout::Layered => write!(f, "按层分目录"),
+            Layout::Nested => write!(f, "原地嵌套"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod layout_test {
+    use super::*;
+
+    #[test]
+    fn test_layer_paths() {
+        let source = Path::new("/source");
+        let target = Path::new("/output");
+
+        assert_eq!(
+            Layout::Layered.layer_paths(source, target, 2),
+            (PathBuf::from("/output/1"), PathBuf::from("/output/2"))
+        );
+        assert_eq!(
+            Layout::Nested.layer_paths(source, target, 1),
+            (PathBuf::from("/source"), PathBuf::from("/output"))
+        );
+        assert_eq!(
+            Layout::Nested.layer_paths(source, target, 3),
+            (PathBuf::from("/output"), PathBuf::from("/output"))
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+use super::backend::{BackendRegistry, ExtractReport};
+use super::detect::is_solid_stream;
+use super::filter::EntryFilter;
+use super::volume::Archive;
+
+// 防止压缩炸弹和无限递归, None 表示不限制
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    // 最多解压的层数
+    pub max_depth: Option<usize>,
+    // 整个任务最多解压出来的字节数
+    pub max_total_bytes: Option<u64>,
+    // 单个压缩包解压后大小和压缩包大小的最大比值
+    pub max_ratio: Option<f64>,
+    // 整个任务最多解压出来的文件数
+    pub max_files: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(32),
+            max_total_bytes: None,
+            max_ratio: Some(1000.0),
+            max_files: None,
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Self {
+            max_depth: None,
+            max_total_bytes: None,
+            max_ratio: None,
+            max_files: None,
+        }
+    }
+
+    pub fn check_depth(&self, depth: usize) -> Result<(), Error> {
+        match self.max_depth {
+            Some(max_depth) if depth > max_depth => Err(Error::DepthLimitExceeded(max_depth)),
+            _ => Ok(()),
+        }
+    }
+
+    fn has_archive_limits(&self) -> bool {
+        self.max_total_bytes.is_some() || self.max_ratio.is_some() || self.max_files.is_some()
+    }
+}
+
+// 所有层共享的解压用量, 克隆出来的 Usage 共享同一份计数
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    bytes: Arc<AtomicU64>,
+    files: Arc<AtomicUsize>,
+}
+
+impl Usage {
+    pub fn new(bytes: u64, files: usize) -> Self {
+        Self {
+            bytes: Arc::new(AtomicU64::new(bytes)),
+            files: Arc::new(AtomicUsize::new(files)),
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::SeqCst)
+    }
+
+    pub fn files(&self) -> usize {
+        self.files.load(Ordering::SeqCst)
+    }
+
+    // 先记上用量再检查, 超出时撤回, 这样并发解压时也不会一起超出
+    // 加起来溢出时不记用量, 直接当作超出限制
+    fn reserve(&self, limits: &Limits, bytes: u64, files: usize) -> Result<Reservation, Error> {
+        let total_bytes = self
+            .bytes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |total| {
+                total.checked_add(bytes)
+            })
+            .map_err(|_| overflow_error(limits))?
+            + bytes;
+        let total_files = self.files.fetch_add(files, Ordering::SeqCst) + files;
+
+        let error = match (limits.max_total_bytes, limits.max_files) {
+            (Some(max_bytes), _) if total_bytes > max_bytes => {
+                Some(Error::SizeLimitExceeded(max_bytes))
+            }
+            (_, Some(max_files)) if total_files > max_files => {
+                Some(Error::FileCountExceeded(max_files))
+            }
+            _ => None,
+        };
+
+        let reservation = Reservation { bytes, files };
+        match error {
+            Some(error) => {
+                self.release(reservation);
+                Err(error)
+            }
+            None => Ok(reservation),
+        }
+    }
+
+    // 解压失败时撤回预留的用量
+    pub fn release(&self, reservation: Reservation) {
+        self.bytes.fetch_sub(reservation.bytes, Ordering::SeqCst);
+        self.files.fetch_sub(reservation.files, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Reservation {
+    bytes: u64,
+    files: usize,
+}
+
+// 解压时按实际写出的字节数检查限制, 压缩包里声明的大小可能是假的
+// 克隆出来的 OutputLimit 共享同一份计数
+#[derive(Debug, Clone, Default)]
+pub struct OutputLimit {
+    archive: PathBuf,
+    compressed: u64,
+    max_ratio: Option<f64>,
+    // 总大小的上限和这个压缩包还能写出的字节数
+    max_bytes: Option<(u64, u64)>,
+    // 总文件数的上限和这个压缩包还能写出的文件数
+    max_files: Option<(usize, usize)>,
+    written: Arc<AtomicU64>,
+}
+
+impl OutputLimit {
+    pub fn add(&self, bytes: u64) -> Result<(), Error> {
+        // tar 按头里声明的大小计数, 可能是伪造的
+        let written = self
+            .written
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |written| {
+                Some(written.saturating_add(bytes))
+            })
+            .unwrap_or_default()
+            .saturating_add(bytes);
+
+        self.check_bytes(written)
+    }
+
+    fn check_bytes(&self, written: u64) -> Result<(), Error> {
+        if let Some(max_ratio) = self.max_ratio {
+            let ratio = written as f64 / self.compressed.max(1) as f64;
+            if ratio > max_ratio {
+                return Err(Error::CompressionRatioExceeded((
+                    self.archive.clone(),
+                    ratio,
+                )));
+            }
+        }
+
+        match self.max_bytes {
+            Some((max_bytes, remaining)) if written > remaining => {
+                Err(Error::SizeLimitExceeded(max_bytes))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // 外部工具写出的内容不经过 add, 解压完后按磁盘上的文件再检查一遍
+    pub fn check_files(&self, files: &[PathBuf]) -> Result<(), Error> {
+        let written = files
+            .iter()
+            .fold(0u64, |sum, file| sum.saturating_add(file_size(file)));
+        self.check_bytes(written)?;
+
+        match self.max_files {
+            Some((max_files, remaining)) if files.len() > remaining => {
+                Err(Error::FileCountExceeded(max_files))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // 换密码重试时重新计数
+    pub fn reset(&self) {
+        self.written.store(0, Ordering::SeqCst);
+    }
+}
+
+// 读取时把读到的字节数计入 OutputLimit, 超出时返回包着限制错误的 io::Error
+pub struct LimitReader<R> {
+    inner: R,
+    limit: OutputLimit,
+}
+
+impl<R> LimitReader<R> {
+    pub fn new(inner: R, limit: OutputLimit) -> Self {
+        Self { inner, limit }
+    }
+}
+
+impl<R: Read> Read for LimitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.limit.add(n as u64).map_err(io::Error::other)?;
+
+        Ok(n)
+    }
+}
+
+// 解压前根据列表检查压缩比并预留用量, 列不出内容时返回 None, 解压后再按实际文件记录
+// 整体压缩的格式列一遍就等于解压一遍, 不预先列出, 只靠解压时的 OutputLimit 检查
+pub fn check_archive(
+    registry: &BackendRegistry,
+    archive: &Archive,
+    filter: &EntryFilter,
+    limits: &Limits,
+    usage: &Usage,
+) -> Result<Option<Reservation>, Error> {
+    if !limits.has_archive_limits() {
+        return Ok(Some(Reservation { bytes: 0, files: 0 }));
+    }
+
+    if is_solid_stream(&archive.path) {
+        return Ok(None);
+    }
+
+    let Ok(entries) = registry.list(archive) else {
+        return Ok(None);
+    };
+
+    // 压缩比按整个压缩包计算, 用量只预留会被解压出来的条目
+    let total_bytes = checked_sum(entries.iter().map(|entry| entry.size))
+        .ok_or_else(|| overflow_error(limits))?;
+    let compressed: u64 = archive.volumes.iter().map(|volume| file_size(volume)).sum();
+
+    if let Some(max_ratio) = limits.max_ratio {
+        let ratio = total_bytes as f64 / compressed.max(1) as f64;
+        if ratio > max_ratio {
+            return Err(Error::CompressionRatioExceeded((
+                archive.path.clone(),
+                ratio,
+            )));
+        }
+    }
+
+    let matcher = filter.matcher()?;
+    let selected: Vec<_> = entries
+        .iter()
+        .filter(|entry| !entry.is_dir && matcher.is_match(&entry.path))
+        .collect();
+    let bytes = checked_sum(selected.iter().map(|entry| entry.size))
+        .ok_or_else(|| overflow_error(limits))?;
+
+    usage.reserve(limits, bytes, selected.len()).map(Some)
+}
+
+// 声明的大小是压缩包自己写的, 可能是伪造的, 加起来溢出时返回 None
+fn checked_sum(mut sizes: impl Iterator<Item = u64>) -> Option<u64> {
+    sizes.try_fold(0u64, |sum, size| sum.checked_add(size))
+}
+
+// 大小加起来溢出 u64, 不管有没有设置总大小的上限都当作超出
+fn overflow_error(limits: &Limits) -> Error {
+    Error::SizeLimitExceeded(limits.max_total_bytes.unwrap_or(u64::MAX))
+}
+
+// 这个压缩包解压时的限制, 预留的用量已经算在 usage 里了, 要先扣掉
+pub fn output_limit(
+    archive: &Archive,
+    limits: &Limits,
+    usage: &Usage,
+    reservation: Option<Reservation>,
+) -> OutputLimit {
+    let used = usage
+        .bytes()
+        .saturating_sub(reservation.map_or(0, |reservation| reservation.bytes));
+    let used_files = usage
+        .files()
+        .saturating_sub(reservation.map_or(0, |reservation| reservation.files));
+
+    OutputLimit {
+        archive: archive.path.clone(),
+        compressed: archive.volumes.iter().map(|volume| file_size(volume)).sum(),
+        max_ratio: limits.max_ratio,
+        max_bytes: limits
+            .max_total_bytes
+            .map(|max_bytes| (max_bytes, max_bytes.saturating_sub(used))),
+        max_files: limits
+            .max_files
+            .map(|max_files| (max_files, max_files.saturating_sub(used_files))),
+        written: Arc::default(),
+    }
+}
+
+// 按实际解压出来的文件记录用量, 预留的用量只是压缩包声明的大小, 要先撤回
+pub fn record_report(report: &ExtractReport, limits: &Limits, usage: &Usage) -> Result<(), Error> {
+    let bytes = report.files.iter().map(|file| file_size(file)).sum();
+
+    usage.reserve(limits, bytes, report.files.len()).map(|_| ())
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod limits_test {
+    use super::*;
+
+    #[test]
+    fn test_usage_reserve() {
+        let limits = Limits {
+            max_total_bytes: Some(100),
+            max_files: Some(3),
+            ..Limits::unlimited()
+        };
+        let usage = Usage::default();
+
+        assert!(usage.reserve(&limits, 60, 1).is_ok());
+        assert!(matches!(
+            usage.reserve(&limits, 60, 1),
+            Err(Error::SizeLimitExceeded(100))
+        ));
+        assert_eq!(usage.bytes(), 60);
+
+        assert!(usage.reserve(&limits, 10, 2).is_ok());
+        assert!(matches!(
+            usage.reserve(&limits, 10, 1),
+            Err(Error::FileCountExceeded(3))
+        ));
+        assert_eq!(usage.files(), 3);
+    }
+
+    #[test]
+    fn test_declared_size_overflow() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("huge.zip"));
+        crate::zip::space::space_test::write_zip64_listing(
+            &archive.path,
+            &["a.bin", "b.bin"],
+            1 << 63,
+        );
+
+        let registry = BackendRegistry::default();
+        let usage = Usage::default();
+        for limits in [Limits::default(), Limits::unlimited()] {
+            let limits = Limits {
+                max_files: Some(10),
+                ..limits
+            };
+            let result = check_archive(
+                &registry,
+                &archive,
+                &EntryFilter::default(),
+                &limits,
+                &usage,
+            );
+            assert!(matches!(result, Err(Error::SizeLimitExceeded(u64::MAX))));
+        }
+        assert_eq!(usage.bytes(), 0);
+
+        // 已经记上的用量加上新的用量溢出时也不能记上
+        let usage = Usage::new(u64::MAX, 0);
+        assert!(matches!(
+            usage.reserve(&Limits::unlimited(), 1, 1),
+            Err(Error::SizeLimitExceeded(u64::MAX))
+        ));
+        assert_eq!(usage.files(), 0);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_solid_stream_not_listed() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("bomb.tar.gz"));
+        let encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&archive.path).unwrap(),
+            flate2::Compression::best(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5 * 1024 * 1024);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bomb.bin", io::repeat(0).take(5 * 1024 * 1024))
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        // 按列表算压缩比会超出, 没有列出就不会在这里报错
+        let usage = Usage::default();
+        let result = check_archive(
+            &BackendRegistry::default(),
+            &archive,
+            &EntryFilter::default(),
+            &Limits::default(),
+            &usage,
+        );
+        assert!(matches!(result, Ok(None)));
+        assert_eq!(usage.bytes(), 0);
+
+        // 解压时照样按实际写出的字节数检查
+        let limit = output_limit(&archive, &Limits::default(), &usage, None);
+        assert!(matches!(
+            limit.add(5 * 1024 * 1024),
+            Err(Error::CompressionRatioExceeded(_))
+        ));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_check_depth() {
+        let limits = Limits::default();
+        assert!(limits.check_depth(32).is_ok());
+        assert!(matches!(
+            limits.check_depth(33),
+            Err(Error::DepthLimitExceeded(32))
+        ));
+        assert!(Limits::unlimited().check_depth(1000).is_ok());
+    }
+}
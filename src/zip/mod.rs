@@ -1,48 +1,324 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use iced::futures::{SinkExt, Stream, StreamExt};
-use iced::stream::try_channel;
-use iced::Subscription;
+use futures::channel::mpsc;
+use futures::{stream, Future, SinkExt, Stream, StreamExt};
+use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 
 use crate::error::Error;
 
+pub mod backend;
+mod conflict;
+mod detect;
+mod filter;
+mod layout;
+mod limits;
+mod scan;
+mod space;
 mod utils;
+mod volume;
 
-use utils::{change_path_root, collect_compressed_files_in_dir, unzip_file};
+pub use conflict::ConflictPolicy;
+pub use detect::DetectPolicy;
+pub use filter::EntryFilter;
+pub use layout::Layout;
+pub use limits::{Limits, Usage};
+pub use scan::ScanPolicy;
+pub use utils::{
+    archive_output_dir, collect_compressed_files, format_unix_time, parse_date, read_password_file,
+};
+pub use volume::Archive;
+
+pub use backend::ArchiveEntry;
+
+use backend::{
+    BackendRegistry, CancelToken, ExtractOptions, ExtractProgress, ExtractReport, ProgressSink,
+};
+use limits::OutputLimit;
+use utils::collect_compressed_files_in_dir;
+
+#[derive(Debug, Clone, Default)]
+pub struct UnzipOptions {
+    // 依次尝试的候选密码
+    pub passwords: Vec<String>,
+    pub detect_policy: DetectPolicy,
+    // 同一层最多同时解压的压缩包数量, None 时为 CPU 核心数
+    pub concurrency: Option<usize>,
+    // 取消后正在运行的解压进程会被杀掉, 排队中的压缩包不再解压
+    pub cancel: CancelToken,
+    // 取消时保留已经解压出来的部分文件
+    pub keep_partial: bool,
+    // 暂停后正在解压的压缩包会继续解压完, 排队中的压缩包返回 Error::Paused
+    pub pause: CancelToken,
+    // 恢复会话时已经处理过的压缩包, 不会再解压, 也不会有 Zipping 事件
+    pub skip: Vec<PathBuf>,
+    // 解压成功后删除压缩包 (包括所有分卷)
+    pub delete_archives: bool,
+    // 只在这些文件中查找压缩包, 一般是上一层解压出来的文件; None 时遍历整个目录
+    pub candidates: Option<Vec<PathBuf>>,
+    pub limits: Limits,
+    // 多层之间共享, 用来检查 limits 中的总大小和总文件数
+    pub usage: Usage,
+    // 不检查目标磁盘的剩余空间
+    pub ignore_disk_space: bool,
+    // 只解压匹配的条目
+    pub filter: EntryFilter,
+    // 每一层挑选压缩包的规则
+    pub scan: ScanPolicy,
+    // 输出目录中已有同名文件时的处理方式
+    pub conflict: ConflictPolicy,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOutcome {
+    // 解压成功时使用的密码, 没有加密时为 None
+    pub password: Option<String>,
+    pub report: ExtractReport,
+}
+
+// 一个压缩包的内容列表, 列不出来时 (例如加密了文件名) 为错误
+#[derive(Debug, Clone)]
+pub struct ArchiveListing {
+    pub archive: Archive,
+    pub entries: Result<Vec<ArchiveEntry>, Error>,
+}
 
 #[derive(Debug, Clone)]
 pub enum Progress {
     EmptyZips,
     Searching {
-        zip_files: Vec<PathBuf>,
+        zip_files: Vec<Archive>,
     },
     Zipping {
         file_id: usize,
-        state: Result<(), Error>,
+        state: Result<ExtractOutcome, Error>,
+    },
+    // 解压前检查磁盘空间, 已经列出了 checked 个压缩包的内容
+    CheckingSpace {
+        checked: usize,
+        total: usize,
+    },
+    // 压缩包拿到了解压名额, 开始解压
+    Extracting {
+        file_id: usize,
+    },
+    // 正在解压的压缩包的字节进度
+    Transferring {
+        file_id: usize,
+        progress: ExtractProgress,
     },
     Finished,
 }
 
-pub fn run_zip_dir(
-    id: usize,
+pub fn default_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+// 先不带密码解压, 需要密码时按顺序尝试候选密码
+fn extract_with_passwords(
+    registry: &BackendRegistry,
+    archive: &Archive,
+    output_dir: &Path,
+    passwords: &[String],
+    base_opts: &ExtractOptions,
+) -> Result<ExtractOutcome, Error> {
+    let candidates = std::iter::once(None).chain(passwords.iter().map(Some));
+
+    for password in candidates {
+        let opts = ExtractOptions {
+            password: password.cloned(),
+            ..base_opts.clone()
+        };
+
+        match registry.extract(archive, output_dir, &opts) {
+            Ok(report) => {
+                return Ok(ExtractOutcome {
+                    password: password.cloned(),
+                    report,
+                })
+            }
+            Err(Error::PasswordRequired(_) | Error::WrongPassword(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(Error::PasswordRequired(archive.path.clone()))
+}
+
+// 后端的解压都是同步的 (读文件, 等待 7z 进程), 放到阻塞线程池中执行, 避免卡住运行时
+async fn run_blocking<T, F>(f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::IoError(e.to_string()))?
+}
+
+// 解压失败或取消时, 解压了一半的文件由 BackendRegistry 丢掉, 这里只删除这次新建的空目录
+// 输出目录里可能有用户原来的文件, 不能整个删除, remove_dir 遇到非空目录会失败
+fn clean_failed<T>(state: Result<T, Error>, output_dir: &Path, created: bool) -> Result<T, Error> {
+    if state.is_err() && created {
+        let _ = std::fs::remove_dir(output_dir);
+    }
+
+    state
+}
+
+fn delete_archive(archive: &Archive) -> Result<(), Error> {
+    for volume in &archive.volumes {
+        std::fs::remove_file(volume)?;
+    }
+
+    Ok(())
+}
+
+// 解压单个压缩包, 前后加上限制检查, 取消清理和删除压缩包
+fn extract_archive(
+    registry: &BackendRegistry,
+    archive: &Archive,
+    output_dir: &Path,
+    options: &UnzipOptions,
+    extract: impl FnOnce(OutputLimit) -> Result<ExtractOutcome, Error>,
+) -> Result<ExtractOutcome, Error> {
+    let state = extract_with_limits(registry, archive, output_dir, options, extract);
+    registry.forget(archive);
+
+    state
+}
+
+fn extract_with_limits(
+    registry: &BackendRegistry,
+    archive: &Archive,
+    output_dir: &Path,
+    options: &UnzipOptions,
+    extract: impl FnOnce(OutputLimit) -> Result<ExtractOutcome, Error>,
+) -> Result<ExtractOutcome, Error> {
+    let reservation = limits::check_archive(
+        registry,
+        archive,
+        &options.filter,
+        &options.limits,
+        &options.usage,
+    )?;
+
+    // generate new dir
+    let created = !output_dir.exists();
+    std::fs::create_dir_all(output_dir)?;
+
+    let output_limit = limits::output_limit(archive, &options.limits, &options.usage, reservation);
+    let state = clean_failed(extract(output_limit), output_dir, created);
+    if let Some(reservation) = reservation {
+        options.usage.release(reservation);
+    }
+    let outcome = state?;
+    limits::record_report(&outcome.report, &options.limits, &options.usage)?;
+
+    if options.delete_archives {
+        delete_archive(archive)?;
+    }
+
+    Ok(outcome)
+}
+
+// 列出单个压缩包的内容, 不会往磁盘写任何东西
+pub async fn list_archive(archive: Archive) -> Result<Vec<ArchiveEntry>, Error> {
+    run_blocking(move || BackendRegistry::default().list(&archive)).await
+}
+
+// 列出 source_dir 中所有压缩包的内容, 某个压缩包列不出来不影响其他的
+pub async fn list_dir(
     source_dir: PathBuf,
-    target_dir: PathBuf,
-    default_password: Option<String>,
-) -> iced::Subscription<(usize, Result<Progress, Error>)> {
-    Subscription::run_with_id(
-        id,
-        unzip_dir_s(source_dir, target_dir, default_password).map(move |progress| (id, progress)),
-    )
+    detect_policy: DetectPolicy,
+    scan_policy: ScanPolicy,
+) -> Result<Vec<ArchiveListing>, Error> {
+    run_blocking(move || {
+        let registry = BackendRegistry::default();
+        let archives = collect_compressed_files_in_dir(&source_dir, detect_policy, &scan_policy)?;
+
+        Ok(archives
+            .into_iter()
+            .map(|archive| ArchiveListing {
+                entries: registry.list(&archive),
+                archive,
+            })
+            .collect())
+    })
+    .await
+}
+
+// 用指定的密码重新解压单个压缩包
+pub async fn retry_archive(
+    archive: Archive,
+    output_dir: PathBuf,
+    password: String,
+    options: UnzipOptions,
+) -> Result<ExtractOutcome, Error> {
+    run_blocking(move || {
+        let registry = BackendRegistry::default();
+
+        extract_archive(&registry, &archive, &output_dir, &options, |output_limit| {
+            let opts = ExtractOptions {
+                password: Some(password.clone()),
+                cancel: options.cancel.clone(),
+                filter: options.filter.clone(),
+                conflict: options.conflict,
+                keep_partial: options.keep_partial,
+                output_limit,
+                ..Default::default()
+            };
+
+            Ok(ExtractOutcome {
+                report: registry.extract(&archive, &output_dir, &opts)?,
+                password: Some(password.clone()),
+            })
+        })
+    })
+    .await
+}
+
+// 和 iced::stream::try_channel 一样, f 通过 sender 发送进度, 返回的错误作为最后一项
+fn try_channel<T, F>(
+    size: usize,
+    f: impl FnOnce(mpsc::Sender<T>) -> F,
+) -> impl Stream<Item = Result<T, Error>>
+where
+    F: Future<Output = Result<(), Error>>,
+{
+    let (sender, receiver) = mpsc::channel(size);
+
+    let runner = stream::once(f(sender)).filter_map(|result| async move { result.err().map(Err) });
+
+    stream::select(receiver.map(Ok), runner)
 }
 
-fn unzip_dir_s(
+// 解压 source_dir 中的所有压缩文件到 target_dir, 不依赖任何界面库
+pub fn unzip_dir_s(
     source_dir: PathBuf,
     target_dir: PathBuf,
-    default_password: Option<String>,
+    options: UnzipOptions,
 ) -> impl Stream<Item = Result<Progress, Error>> {
     try_channel(1, move |mut output| async move {
-        let compressed_files = collect_compressed_files_in_dir(&source_dir)?;
+        // 上次中断时留下的临时目录里可能有压缩包, 不能被当作解压出来的文件
+        backend::remove_stale_staging(&target_dir);
+
+        let compressed_files = match &options.candidates {
+            // 上一层解压出来的文件按同样的规则筛选
+            Some(candidates) => {
+                let matcher = options.scan.matcher()?;
+                collect_compressed_files(candidates, options.detect_policy)
+                    .into_iter()
+                    .filter(|archive| matcher.accepts(&source_dir, archive))
+                    .collect()
+            }
+            None => {
+                collect_compressed_files_in_dir(&source_dir, options.detect_policy, &options.scan)?
+            }
+        };
         if compressed_files.is_empty() {
             let _ = output.send(Progress::EmptyZips).await;
 
@@ -54,37 +330,125 @@ fn unzip_dir_s(
             })
             .await;
 
+        let registry = Arc::new(BackendRegistry::default());
+
+        // 空间不够时整层都不解压, 以 Error::InsufficientSpace 结束
+        if !options.ignore_disk_space {
+            let registry = registry.clone();
+            let target_dir = target_dir.clone();
+            let queued: Vec<Archive> = compressed_files
+                .iter()
+                .filter(|archive| !options.skip.contains(&archive.path))
+                .cloned()
+                .collect();
+            let filter = options.filter.clone();
+            let cancel = options.cancel.clone();
+            let sender = std::sync::Mutex::new(output.clone());
+            run_blocking(move || {
+                let total = queued.len();
+                space::check_disk_space(
+                    &registry,
+                    &queued,
+                    &filter,
+                    &target_dir,
+                    &cancel,
+                    |checked| {
+                        if let Ok(mut sender) = sender.lock() {
+                            let _ = sender.try_send(Progress::CheckingSpace { checked, total });
+                        }
+                    },
+                )
+            })
+            .await?;
+        }
+
+        let limit = options.concurrency.unwrap_or_else(default_concurrency);
+        let semaphore = Arc::new(Semaphore::new(limit.max(1)));
         let mut set = JoinSet::new();
 
         for (index, compressed_file) in compressed_files.into_iter().enumerate() {
-            // generate new dir
-            let file_base_name = compressed_file.file_stem().unwrap();
-
-            let new_root_file = change_path_root(&source_dir, &compressed_file, &target_dir);
-            let mut new_root_file_comp = new_root_file.components();
-            new_root_file_comp.next_back();
+            if options.skip.contains(&compressed_file.path) {
+                continue;
+            }
 
-            let output_dir = new_root_file_comp.as_path().join(file_base_name);
-            println!("file: {:?} output_dir: {:?}", compressed_file, output_dir);
-            std::fs::create_dir_all(&output_dir)?;
+            let output_dir = archive_output_dir(&source_dir, &compressed_file, &target_dir);
 
-            let password = default_password.clone();
+            let registry = registry.clone();
+            let semaphore = semaphore.clone();
+            let options = options.clone();
+            let mut output = output.clone();
             set.spawn(async move {
-                (
-                    index,
-                    unzip_file(&compressed_file, &output_dir, password).await,
-                )
+                let _permit = semaphore.acquire_owned().await.ok();
+                if options.cancel.is_cancelled() {
+                    return (index, Err(Error::Cancelled));
+                }
+                if options.pause.is_cancelled() {
+                    return (index, Err(Error::Paused));
+                }
+                let _ = output.send(Progress::Extracting { file_id: index }).await;
+
+                // 进度是在阻塞线程里上报的, 通道满了就丢掉这一次, 反正很快会有下一次
+                let sender = std::sync::Mutex::new(output.clone());
+                let progress = ProgressSink::new(move |progress| {
+                    if let Ok(mut sender) = sender.lock() {
+                        let _ = sender.try_send(Progress::Transferring {
+                            file_id: index,
+                            progress,
+                        });
+                    }
+                });
+                let base_opts = ExtractOptions {
+                    password: None,
+                    cancel: options.cancel.clone(),
+                    progress,
+                    filter: options.filter.clone(),
+                    conflict: options.conflict,
+                    keep_partial: options.keep_partial,
+                    output_limit: Default::default(),
+                    listing: None,
+                };
+
+                // 阻塞线程不会被 abort, 在里面清理才能保证取消后删除输出目录
+                let state = run_blocking(move || {
+                    extract_archive(
+                        &registry,
+                        &compressed_file,
+                        &output_dir,
+                        &options,
+                        |output_limit| {
+                            extract_with_passwords(
+                                &registry,
+                                &compressed_file,
+                                &output_dir,
+                                &options.passwords,
+                                &ExtractOptions {
+                                    output_limit,
+                                    ..base_opts
+                                },
+                            )
+                        },
+                    )
+                })
+                .await;
+
+                (index, state)
             });
         }
 
         while let Some(res) = set.join_next().await {
-            let res = res.unwrap();
-            let _ = output
-                .send(Progress::Zipping {
-                    file_id: res.0,
-                    state: res.1,
-                })
-                .await;
+            if options.cancel.is_cancelled() {
+                set.abort_all();
+            }
+
+            // 被 abort 的任务没有结果
+            let Ok((file_id, state)) = res else {
+                continue;
+            };
+            let _ = output.send(Progress::Zipping { file_id, state }).await;
+        }
+
+        if options.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
         }
 
         let _ = output.send(Progress::Finished).await;
@@ -92,3 +456,502 @@ fn unzip_dir_s(
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod unzip_test {
+    use super::*;
+    use backend::{ArchiveBackend, ArchiveEntry};
+
+    // 写出一个文件后返回取消, 模拟解压到一半被取消
+    struct CancelledBackend;
+
+    impl ArchiveBackend for CancelledBackend {
+        fn name(&self) -> &'static str {
+            "cancelled"
+        }
+
+        fn can_handle(&self, _path: &Path) -> bool {
+            true
+        }
+
+        fn list(&self, _path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+            Ok(Vec::new())
+        }
+
+        fn extract(
+            &self,
+            _path: &Path,
+            dest: &Path,
+            _opts: &ExtractOptions,
+        ) -> Result<ExtractReport, Error> {
+            std::fs::write(dest.join("partial.txt"), "partial")?;
+            Err(Error::Cancelled)
+        }
+    }
+
+    #[test]
+    fn test_cancel_keeps_existing_files() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("a.zip"));
+        let output_dir = temp_dir.path().join("a");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::write(output_dir.join("old.txt"), "old").unwrap();
+
+        let mut registry = BackendRegistry::new();
+        registry.register(CancelledBackend);
+
+        for keep_partial in [false, true] {
+            let options = UnzipOptions {
+                keep_partial,
+                limits: Limits::unlimited(),
+                ..Default::default()
+            };
+            let opts = ExtractOptions {
+                keep_partial,
+                ..Default::default()
+            };
+
+            let state = extract_archive(&registry, &archive, &output_dir, &options, |_| {
+                extract_with_passwords(&registry, &archive, &output_dir, &[], &opts)
+            });
+
+            assert!(matches!(state, Err(Error::Cancelled)));
+            assert_eq!(
+                std::fs::read_to_string(output_dir.join("old.txt")).unwrap(),
+                "old"
+            );
+            assert_eq!(output_dir.join("partial.txt").exists(), keep_partial);
+            // 临时目录不会留下来
+            assert_eq!(
+                std::fs::read_dir(&output_dir).unwrap().count(),
+                1 + keep_partial as usize
+            );
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_remove_stale_staging() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let target = temp_dir.path();
+        std::fs::create_dir_all(target.join(".zipdive-a.zip").join("dir")).unwrap();
+        std::fs::write(target.join(".zipdive-a.zip").join("b.zip"), "").unwrap();
+        std::fs::create_dir_all(target.join("a").join(".zipdive-c.7z")).unwrap();
+        std::fs::write(target.join("a").join(".zipdive-note.txt"), "").unwrap();
+
+        backend::remove_stale_staging(target);
+
+        assert!(!target.join(".zipdive-a.zip").exists());
+        assert!(!target.join("a").join(".zipdive-c.7z").exists());
+        // 只删除目录
+        assert!(target.join("a").join(".zipdive-note.txt").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    // 条目头里声明只有 10 字节, 实际解压出来 5 MB 的压缩炸弹
+    fn create_bomb(path: &Path) {
+        let mut zip = ::zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        zip.start_file("bomb.bin", Default::default()).unwrap();
+        std::io::Write::write_all(&mut zip, &vec![0; 5 * 1024 * 1024]).unwrap();
+        zip.finish().unwrap();
+
+        let mut bytes = std::fs::read(path).unwrap();
+        for (signature, offset) in [(b"PK\x03\x04", 22), (b"PK\x01\x02", 24)] {
+            let start = bytes
+                .windows(4)
+                .position(|window| window == signature)
+                .unwrap();
+            bytes[start + offset..start + offset + 4].copy_from_slice(&10u32.to_le_bytes());
+        }
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_streaming_limits() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("bomb.zip"));
+        create_bomb(&archive.path);
+        let registry = BackendRegistry::default();
+
+        let by_ratio = Limits {
+            max_ratio: Some(100.0),
+            ..Limits::unlimited()
+        };
+        let by_size = Limits {
+            max_total_bytes: Some(1024 * 1024),
+            ..Limits::unlimited()
+        };
+        for limits in [by_ratio, by_size] {
+            let output_dir = temp_dir.path().join("bomb");
+            let options = UnzipOptions {
+                limits,
+                ..Default::default()
+            };
+
+            let state =
+                extract_archive(&registry, &archive, &output_dir, &options, |output_limit| {
+                    let opts = ExtractOptions {
+                        output_limit,
+                        ..Default::default()
+                    };
+                    extract_with_passwords(&registry, &archive, &output_dir, &[], &opts)
+                });
+
+            match limits.max_ratio {
+                Some(_) => assert!(matches!(state, Err(Error::CompressionRatioExceeded(_)))),
+                None => assert!(matches!(state, Err(Error::SizeLimitExceeded(_)))),
+            }
+            // 解压到一半的文件不会留下来, 这次新建的输出目录也删掉
+            assert!(!output_dir.exists());
+            assert_eq!(options.usage.bytes(), 0);
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    // 列表里只声明了 1 字节, 实际写出 3 个 1000 字节的文件, 像外部工具一样不经过 output_limit
+    struct ForgedSizeBackend;
+
+    impl ArchiveBackend for ForgedSizeBackend {
+        fn name(&self) -> &'static str {
+            "forged"
+        }
+
+        fn can_handle(&self, _path: &Path) -> bool {
+            true
+        }
+
+        fn list(&self, _path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+            Ok(vec![ArchiveEntry {
+                path: PathBuf::from("a.bin"),
+                size: 1,
+                ..Default::default()
+            }])
+        }
+
+        fn extract(
+            &self,
+            _path: &Path,
+            dest: &Path,
+            _opts: &ExtractOptions,
+        ) -> Result<ExtractReport, Error> {
+            for name in ["a.bin", "b.bin", "c.bin"] {
+                std::fs::write(dest.join(name), vec![0; 1000])?;
+            }
+            Ok(ExtractReport::from_dir(dest))
+        }
+    }
+
+    #[test]
+    fn test_limits_on_forged_sizes() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("forged.7z"));
+        std::fs::write(&archive.path, [0; 10]).unwrap();
+
+        let mut registry = BackendRegistry::new();
+        registry.register(ForgedSizeBackend);
+
+        let by_ratio = Limits {
+            max_ratio: Some(100.0),
+            ..Limits::unlimited()
+        };
+        let by_size = Limits {
+            max_total_bytes: Some(2000),
+            ..Limits::unlimited()
+        };
+        let by_files = Limits {
+            max_files: Some(2),
+            ..Limits::unlimited()
+        };
+        for limits in [by_ratio, by_size, by_files] {
+            // 原来就有的输出目录不删
+            let output_dir = temp_dir.path().join("forged");
+            std::fs::create_dir_all(&output_dir).unwrap();
+            let options = UnzipOptions {
+                limits,
+                ..Default::default()
+            };
+
+            let state =
+                extract_archive(&registry, &archive, &output_dir, &options, |output_limit| {
+                    let opts = ExtractOptions {
+                        output_limit,
+                        ..Default::default()
+                    };
+                    extract_with_passwords(&registry, &archive, &output_dir, &[], &opts)
+                });
+
+            assert!(match state {
+                Err(Error::CompressionRatioExceeded(_)) => limits.max_ratio.is_some(),
+                Err(Error::SizeLimitExceeded(2000)) => limits.max_total_bytes.is_some(),
+                Err(Error::FileCountExceeded(2)) => limits.max_files.is_some(),
+                _ => false,
+            });
+            assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 0);
+            assert_eq!(options.usage.bytes(), 0);
+            assert_eq!(options.usage.files(), 0);
+            assert!(registry.cached_listing(&archive).is_none());
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_wrong_password_passing_check() {
+        use ::zip::unstable::write::FileOptionsExt;
+
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("secret.zip"));
+        let output_dir = temp_dir.path().join("secret");
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        let mut zip = ::zip::ZipWriter::new(std::fs::File::create(&archive.path).unwrap());
+        let file_options = ::zip::write::FileOptions::default()
+            .compression_method(::zip::CompressionMethod::Stored)
+            .with_deprecated_encryption(b"right");
+        zip.start_file("secret.txt", file_options).unwrap();
+        std::io::Write::write_all(&mut zip, b"secret data").unwrap();
+        zip.finish().unwrap();
+
+        // ZipCrypto 只有一个校验字节, 很容易找到能通过检查的错误密码
+        let mut reader =
+            ::zip::ZipArchive::new(std::fs::File::open(&archive.path).unwrap()).unwrap();
+        let fake = (0..)
+            .map(|n| format!("wrong{}", n))
+            .find(|password| {
+                reader
+                    .by_index_decrypt(0, password.as_bytes())
+                    .is_ok_and(|entry| entry.is_ok())
+            })
+            .unwrap();
+
+        let registry = BackendRegistry::default();
+        let passwords = [fake.clone(), "right".to_string()];
+        let outcome = extract_with_passwords(
+            &registry,
+            &archive,
+            &output_dir,
+            &passwords,
+            &ExtractOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(outcome.password.as_deref(), Some("right"));
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("secret.txt")).unwrap(),
+            "secret data"
+        );
+
+        let state = extract_with_passwords(
+            &registry,
+            &archive,
+            &output_dir,
+            &[fake],
+            &ExtractOptions::default(),
+        );
+        assert!(matches!(state, Err(Error::PasswordRequired(_))));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_aes_passwords() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("aes_archive.zip"));
+        let output_dir = temp_dir.path().join("aes_archive");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/aes_archive.zip"),
+            &archive.path,
+        )
+        .unwrap();
+
+        let registry = BackendRegistry::default();
+        let passwords = ["wrong".to_string(), "helloworld".to_string()];
+        let outcome = extract_with_passwords(
+            &registry,
+            &archive,
+            &output_dir,
+            &passwords,
+            &ExtractOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(outcome.password.as_deref(), Some("helloworld"));
+        assert_eq!(outcome.report.files.len(), 4);
+        assert!(std::fs::read_to_string(output_dir.join("secret_data_256"))
+            .unwrap()
+            .starts_with("Lorem ipsum dolor sit amet"));
+
+        temp_dir.close().unwrap();
+    }
+
+    fn write_zip(path: &Path, name: &str, content: &[u8]) {
+        let mut zip = ::zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        zip.start_file(name, Default::default()).unwrap();
+        std::io::Write::write_all(&mut zip, content).unwrap();
+        zip.finish().unwrap();
+    }
+
+    // 和命令行一样一层一层地解压, 返回每一层解压出来的文件数
+    fn unzip_layers(source: &Path, target: &Path, layout: Layout, delete: bool) -> Vec<usize> {
+        let mut options = UnzipOptions {
+            ignore_disk_space: true,
+            ..Default::default()
+        };
+        let mut produced_counts = Vec::new();
+
+        for depth in 1.. {
+            let (input_path, output_path) = layout.layer_paths(source, target, depth);
+            options.delete_archives = delete && layout == Layout::Nested && depth > 1;
+
+            let events: Vec<_> = tokio_test::block_on(
+                unzip_dir_s(input_path, output_path, options.clone()).collect(),
+            );
+            let mut produced = Vec::new();
+            for event in events {
+                match event.unwrap() {
+                    Progress::EmptyZips => return produced_counts,
+                    Progress::Zipping { state, .. } => produced.extend(state.unwrap().report.files),
+                    _ => {}
+                }
+            }
+
+            produced_counts.push(produced.len());
+            options.candidates = Some(produced);
+        }
+
+        produced_counts
+    }
+
+    #[test]
+    fn test_nested_layout_deletes_archives() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        let target = temp_dir.path().join("output");
+        std::fs::create_dir_all(&source).unwrap();
+
+        let inner = temp_dir.path().join("inner.zip");
+        write_zip(&inner, "hello.txt", b"hello");
+        write_zip(
+            &source.join("outer.zip"),
+            "inner.zip",
+            &std::fs::read(&inner).unwrap(),
+        );
+
+        assert_eq!(unzip_layers(&source, &target, Layout::Nested, true), [1, 1]);
+
+        // 第一层的压缩包在输入目录里, 不会被删除; 之后解压出来的压缩包解压成功后删除
+        assert!(source.join("outer.zip").exists());
+        assert!(!target.join("outer").join("inner.zip").exists());
+        assert_eq!(
+            std::fs::read_to_string(target.join("outer").join("inner").join("hello.txt")).unwrap(),
+            "hello"
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_cancel_layer() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        let target = temp_dir.path().join("output");
+        std::fs::create_dir_all(&source).unwrap();
+        write_zip(&source.join("a.zip"), "a.txt", b"a");
+
+        let options = UnzipOptions {
+            ignore_disk_space: true,
+            ..Default::default()
+        };
+        options.cancel.cancel();
+
+        let events: Vec<_> =
+            tokio_test::block_on(unzip_dir_s(source, target.clone(), options).collect());
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Ok(Progress::Zipping {
+                state: Err(Error::Cancelled),
+                ..
+            })
+        )));
+        assert!(matches!(events.last(), Some(Err(Error::Cancelled))));
+        assert!(!target.join("a").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_skip_and_pause() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        let target = temp_dir.path().join("output");
+        std::fs::create_dir_all(&source).unwrap();
+        write_zip(&source.join("a.zip"), "a.txt", b"a");
+        write_zip(&source.join("b.zip"), "b.txt", b"b");
+
+        // 恢复会话时跳过已经处理过的压缩包
+        let options = UnzipOptions {
+            ignore_disk_space: true,
+            skip: vec![source.join("a.zip")],
+            ..Default::default()
+        };
+        let events: Vec<_> =
+            tokio_test::block_on(unzip_dir_s(source.clone(), target.clone(), options).collect());
+        let states: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Ok(Progress::Zipping { state, .. }) => Some(state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(states.len(), 1);
+        assert!(states[0].is_ok());
+        assert!(!target.join("a").exists());
+        assert!(target.join("b").join("b.txt").exists());
+
+        // 暂停后排队中的压缩包都不解压
+        std::fs::remove_dir_all(&target).unwrap();
+        let options = UnzipOptions {
+            ignore_disk_space: true,
+            ..Default::default()
+        };
+        options.pause.cancel();
+        let events: Vec<_> =
+            tokio_test::block_on(unzip_dir_s(source, target.clone(), options).collect());
+        let paused = events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    Ok(Progress::Zipping {
+                        state: Err(Error::Paused),
+                        ..
+                    })
+                )
+            })
+            .count();
+        assert_eq!(paused, 2);
+        assert!(!target.join("a").exists());
+        assert!(!target.join("b").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_cancel_while_reading() {
+        let cancel = CancelToken::default();
+        let mut reader = backend::ProgressReader::new(
+            std::io::Cursor::new(vec![0; 1024]),
+            ProgressSink::default(),
+            cancel.clone(),
+        );
+        cancel.cancel();
+
+        let e = std::io::copy(&mut reader, &mut std::io::sink()).unwrap_err();
+        assert!(matches!(
+            Error::from_archive_io(e, Path::new("a.zip")),
+            Error::Cancelled
+        ));
+    }
+}
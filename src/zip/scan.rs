@@ -0,0 +1,140 @@
+use std::path::{Component, Path};
+use std::time::UNIX_EPOCH;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+use super::volume::Archive;
+
+// 每一层查找压缩包时的规则, 和 EntryFilter 不同, 这里决定哪些压缩包会被解压
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanPolicy {
+    // 跳过名字匹配的目录, 例如 `.git`, `node_modules`
+    pub exclude_dirs: Vec<String>,
+    // 压缩包 (所有分卷加起来) 的大小范围, 单位字节
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    // 修改时间的范围, unix 时间戳, 包含 after 不包含 before
+    pub modified_after: Option<u64>,
+    pub modified_before: Option<u64>,
+    // 跟随符号链接查找, 默认和 WalkDir 一样不跟随
+    pub follow_symlinks: bool,
+}
+
+impl ScanPolicy {
+    pub fn matcher(&self) -> Result<ScanMatcher<'_>, Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.exclude_dirs {
+            builder.add(Glob::new(pattern).map_err(|e| Error::InvalidPattern(e.to_string()))?);
+        }
+
+        Ok(ScanMatcher {
+            policy: self,
+            exclude_dirs: builder
+                .build()
+                .map_err(|e| Error::InvalidPattern(e.to_string()))?,
+        })
+    }
+}
+
+pub struct ScanMatcher<'a> {
+    policy: &'a ScanPolicy,
+    exclude_dirs: GlobSet,
+}
+
+impl ScanMatcher<'_> {
+    pub fn is_excluded_dir(&self, name: &Path) -> bool {
+        self.exclude_dirs.is_match(name)
+    }
+
+    // root 之下的某一级目录被排除, 或者大小, 修改时间不在范围内时返回 false
+    pub fn accepts(&self, root: &Path, archive: &Archive) -> bool {
+        let relative = archive.path.strip_prefix(root).unwrap_or(&archive.path);
+        let in_excluded_dir = relative
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .any(|component| match component {
+                Component::Normal(name) => self.is_excluded_dir(Path::new(name)),
+                _ => false,
+            });
+        if in_excluded_dir {
+            return false;
+        }
+
+        let policy = self.policy;
+        let size: u64 = archive
+            .volumes
+            .iter()
+            .filter_map(|volume| std::fs::metadata(volume).ok())
+            .map(|meta| meta.len())
+            .sum();
+        if policy.min_size.is_some_and(|min| size < min)
+            || policy.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+
+        if policy.modified_after.is_none() && policy.modified_before.is_none() {
+            return true;
+        }
+        let Some(modified) = std::fs::metadata(&archive.path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+        else {
+            return false;
+        };
+
+        policy.modified_after.is_none_or(|after| modified >= after)
+            && policy
+                .modified_before
+                .is_none_or(|before| modified < before)
+    }
+}
+
+#[cfg(test)]
+mod scan_test {
+    use super::*;
+
+    #[test]
+    fn test_scan_policy() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive_path = temp_dir.path().join("a.zip");
+        std::fs::write(&archive_path, [0; 100]).unwrap();
+        let archive = Archive::single(archive_path);
+
+        let policy = ScanPolicy {
+            exclude_dirs: vec![".git".to_string(), "node_*".to_string()],
+            min_size: Some(50),
+            ..Default::default()
+        };
+        let matcher = policy.matcher().unwrap();
+        assert!(matcher.accepts(temp_dir.path(), &archive));
+
+        let excluded_path = temp_dir.path().join("node_modules").join("b.zip");
+        std::fs::create_dir_all(excluded_path.parent().unwrap()).unwrap();
+        std::fs::write(&excluded_path, [0; 100]).unwrap();
+        let excluded = Archive::single(excluded_path);
+        assert!(!matcher.accepts(temp_dir.path(), &excluded));
+        // 只检查 root 之下的目录
+        assert!(matcher.accepts(&temp_dir.path().join("node_modules"), &excluded));
+
+        let policy = ScanPolicy {
+            max_size: Some(50),
+            ..Default::default()
+        };
+        assert!(!policy.matcher().unwrap().accepts(temp_dir.path(), &archive));
+
+        let policy = ScanPolicy {
+            modified_before: Some(0),
+            ..Default::default()
+        };
+        assert!(!policy.matcher().unwrap().accepts(temp_dir.path(), &archive));
+
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use std::path::Path;
+
+use crate::error::Error;
+
+use super::backend::{BackendRegistry, CancelToken};
+use super::detect::is_solid_stream;
+use super::filter::EntryFilter;
+use super::volume::Archive;
+
+// 输出目录可能还没有创建, 往上找到第一个存在的目录来查询剩余空间
+fn available_space(target_dir: &Path) -> Option<u64> {
+    let existing = target_dir.ancestors().find(|dir| dir.exists())?;
+
+    fs2::available_space(existing).ok()
+}
+
+// 解压前根据列表估算需要的空间, 列不出内容的压缩包 (例如加密了文件名) 不计入
+// tar.gz 这类整体压缩的格式列一遍就等于解压一遍, 按压缩包本身的大小估算
+// 每列完一个压缩包调用一次 on_checked, 参数为已经列完的数量
+pub fn check_disk_space(
+    registry: &BackendRegistry,
+    archives: &[Archive],
+    filter: &EntryFilter,
+    target_dir: &Path,
+    cancel: &CancelToken,
+    mut on_checked: impl FnMut(usize),
+) -> Result<(), Error> {
+    let Some(available) = available_space(target_dir) else {
+        return Ok(());
+    };
+    let matcher = filter.matcher()?;
+
+    // 大小是压缩包自己声明的, 可能是伪造的, 加起来不能溢出
+    let mut required: u64 = 0;
+    for (index, archive) in archives.iter().enumerate() {
+        cancel.check()?;
+
+        if is_solid_stream(&archive.path) {
+            required = archive
+                .volumes
+                .iter()
+                .filter_map(|volume| std::fs::metadata(volume).ok())
+                .fold(required, |sum, meta| sum.saturating_add(meta.len()));
+        } else if let Ok(entries) = registry.list(archive) {
+            required = entries
+                .iter()
+                .filter(|entry| matcher.is_match(&entry.path))
+                .fold(required, |sum, entry| sum.saturating_add(entry.size));
+        }
+        on_checked(index + 1);
+    }
+
+    if required > available {
+        return Err(Error::InsufficientSpace((required, available)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod space_test {
+    use super::*;
+    use crate::zip::backend::{ArchiveBackend, ArchiveEntry, ExtractOptions, ExtractReport};
+
+    // 手写一个 zip64 压缩包, 每个条目都声明 size 字节, 实际没有数据
+    pub(crate) fn write_zip64_listing(path: &Path, names: &[&str], size: u64) {
+        let zip64_extra = |bytes: &mut Vec<u8>| {
+            bytes.extend_from_slice(&1u16.to_le_bytes());
+            bytes.extend_from_slice(&16u16.to_le_bytes());
+            bytes.extend_from_slice(&size.to_le_bytes());
+            bytes.extend_from_slice(&0u64.to_le_bytes());
+        };
+        // 版本, 标记, 压缩方式, 时间, 日期, crc, 两个大小都交给 zip64 扩展字段
+        let common = |bytes: &mut Vec<u8>, name: &str| {
+            bytes.extend_from_slice(&45u16.to_le_bytes());
+            bytes.extend_from_slice(&[0; 12]);
+            bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+            bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&20u16.to_le_bytes());
+        };
+
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::new();
+        for name in names {
+            offsets.push(bytes.len() as u32);
+            bytes.extend_from_slice(b"PK\x03\x04");
+            common(&mut bytes, name);
+            bytes.extend_from_slice(name.as_bytes());
+            zip64_extra(&mut bytes);
+        }
+
+        let central_start = bytes.len();
+        for (name, offset) in names.iter().zip(offsets) {
+            bytes.extend_from_slice(b"PK\x01\x02");
+            bytes.extend_from_slice(&45u16.to_le_bytes());
+            common(&mut bytes, name);
+            // 注释长度, 磁盘号, 内部属性, 外部属性
+            bytes.extend_from_slice(&[0; 10]);
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            zip64_extra(&mut bytes);
+        }
+        let central_size = bytes.len() - central_start;
+
+        bytes.extend_from_slice(b"PK\x05\x06");
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(central_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&(central_start as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 2]);
+
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    // 列表中声明了一个很大的文件, 不会真的解压
+    struct HugeBackend;
+
+    impl ArchiveBackend for HugeBackend {
+        fn name(&self) -> &'static str {
+            "huge"
+        }
+
+        fn can_handle(&self, _path: &Path) -> bool {
+            true
+        }
+
+        fn list(&self, _path: &Path) -> Result<Vec<ArchiveEntry>, Error> {
+            Ok(vec![ArchiveEntry {
+                path: "huge.bin".into(),
+                size: u64::MAX / 4,
+                ..Default::default()
+            }])
+        }
+
+        fn extract(
+            &self,
+            _path: &Path,
+            _dest: &Path,
+            _opts: &ExtractOptions,
+        ) -> Result<ExtractReport, Error> {
+            Err(Error::SystemNotSupport)
+        }
+    }
+
+    #[test]
+    fn test_check_disk_space() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut registry = BackendRegistry::new();
+        registry.register(HugeBackend);
+        let archives = vec![
+            Archive::single(temp_dir.path().join("a.zip")),
+            Archive::single(temp_dir.path().join("b.zip")),
+        ];
+
+        let mut checked = Vec::new();
+        let result = check_disk_space(
+            &registry,
+            &archives,
+            &EntryFilter::default(),
+            temp_dir.path(),
+            &CancelToken::default(),
+            |count| checked.push(count),
+        );
+        assert!(matches!(result, Err(Error::InsufficientSpace(_))));
+        assert_eq!(checked, vec![1, 2]);
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let result = check_disk_space(
+            &registry,
+            &archives,
+            &EntryFilter::default(),
+            temp_dir.path(),
+            &cancel,
+            |_| {},
+        );
+        assert!(matches!(result, Err(Error::Cancelled)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_solid_stream_estimate() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut registry = BackendRegistry::new();
+        registry.register(HugeBackend);
+        let archive = Archive::single(temp_dir.path().join("a.tar.gz"));
+        std::fs::write(&archive.path, [0; 100]).unwrap();
+
+        // 不会列出声明的大小, 只按压缩包本身的大小估算
+        let result = check_disk_space(
+            &registry,
+            std::slice::from_ref(&archive),
+            &EntryFilter::default(),
+            temp_dir.path(),
+            &CancelToken::default(),
+            |_| {},
+        );
+        assert!(result.is_ok());
+        assert!(registry.cached_listing(&archive).is_none());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_declared_size_overflow() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let archive = Archive::single(temp_dir.path().join("huge.zip"));
+        write_zip64_listing(&archive.path, &["a.bin", "b.bin"], 1 << 63);
+
+        let registry = BackendRegistry::default();
+        let entries = registry.list(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].size, 1 << 63);
+
+        let result = check_disk_space(
+            &registry,
+            &[archive],
+            &EntryFilter::default(),
+            temp_dir.path(),
+            &CancelToken::default(),
+            |_| {},
+        );
+        assert!(matches!(
+            result,
+            Err(Error::InsufficientSpace((u64::MAX, _)))
+        ));
+
+        temp_dir.close().unwrap();
+    }
+}
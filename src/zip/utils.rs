@@ -1,97 +1,151 @@
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 use walkdir::WalkDir;
 
 use crate::error::Error;
 
-fn is_compressed_file(file: &Path) -> bool {
-    // TODO: 支持其他后缀名压缩文件自动识别
-    // 分为两种方法，一种使用后缀名，一种使用文件头
-
-    let ext = file.extension().unwrap_or_default();
-    let ext_matchp = matches!(
-        ext.to_str(),
-        Some("zip" | "rar" | "7z" | "tar" | "gz" | "bz2")
-    );
-
-    println!("file: {:?} {:?} {:?}", file, ext, ext_matchp);
-
-    ext_matchp
-}
-
-pub fn collect_compressed_files_in_dir(search_dir: &Path) -> Result<Vec<PathBuf>, Error> {
-    // TODO: 支持检测分卷压缩, 合并为同一项
+use super::detect::{is_compressed_file, DetectPolicy};
+use super::scan::ScanPolicy;
+use super::volume::{group_volumes, is_volume_file, Archive};
 
+pub fn collect_compressed_files_in_dir(
+    search_dir: &Path,
+    detect_policy: DetectPolicy,
+    scan_policy: &ScanPolicy,
+) -> Result<Vec<Archive>, Error> {
     if !search_dir.exists() {
         return Err(Error::FileNotExists(search_dir.to_path_buf()));
     }
 
+    let matcher = scan_policy.matcher()?;
+
+    // 排除的目录直接不进入, 不跟随符号链接时链接指向的文件也不算
     let compressed_files = WalkDir::new(search_dir)
+        .follow_links(scan_policy.follow_symlinks)
         .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !matcher.is_excluded_dir(Path::new(entry.file_name()))
+        })
         .filter_map(|e| e.ok())
-        .filter(|entry| entry.path().is_file() && is_compressed_file(entry.path()))
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && (is_volume_file(entry.path()) || is_compressed_file(entry.path(), detect_policy))
+        })
         .map(|entry| entry.path().to_path_buf())
         .collect();
 
-    Ok(compressed_files)
+    Ok(group_volumes(compressed_files)
+        .into_iter()
+        .filter(|archive| matcher.accepts(search_dir, archive))
+        .collect())
 }
 
-pub async fn unzip_file(
-    file_path: &Path,
-    output_dir: &Path,
-    password: Option<String>,
-) -> Result<(), Error> {
-    let output = match std::env::consts::OS {
-        "windows" => {
-            // bandzip
-            let password_command = if let Some(password) = password {
-                format!("-p:\"{}\"", password)
-            } else {
-                String::from("")
-            };
-
-            // TODO: 有密码的压缩文件如果不输入密码的话，不会报错，直接退出
-            let command = format!(
-                "Bandizip.exe x -y {} -o:\"{}\" \"{}\"",
-                password_command,
-                file_path.display(),
-                output_dir.display()
-            );
+// 从给定的文件中挑出压缩包, 已经不存在的文件会被忽略
+pub fn collect_compressed_files(files: &[PathBuf], detect_policy: DetectPolicy) -> Vec<Archive> {
+    let compressed_files = files
+        .iter()
+        .filter(|file| {
+            file.is_file() && (is_volume_file(file) || is_compressed_file(file, detect_policy))
+        })
+        .cloned()
+        .collect();
 
-            Command::new("powershell.exe")
-                .arg("-c")
-                .arg(command)
-                .output()?
-        }
-        "linux" | "macos" => {
-            let mut command = format!(
-                "7z x \"{}\" -o\"{}\"",
-                file_path.display(),
-                output_dir.display()
-            );
+    group_volumes(compressed_files)
+}
 
-            if let Some(password) = password {
-                command = format!("{} -p \"{}\"", command, password);
-            }
+// 密码文件每行一个密码, 忽略空行
+pub fn read_password_file(path: &Path) -> Result<Vec<String>, Error> {
+    let content = std::fs::read_to_string(path)?;
 
-            // TODO: 有密码的压缩文件如果不输入密码的话，会卡住，需要处理并且报错
-            Command::new("bash").arg("-c").arg(command).output()?
-        }
-        _ => {
-            return Err(Error::SystemNotSupport);
-        }
-    };
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
 
-    // linux
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err(Error::ZipError((
-            String::from_utf8_lossy(output.stderr.as_slice()).to_string(),
-            file_path.to_path_buf(),
-        )))
+// UTC 的 unix 时间戳按公历换算成日期
+pub fn format_unix_time(secs: u64) -> String {
+    let days = secs / 86400;
+    let rem = secs % 86400;
+
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// 解析 `YYYY-MM-DD`, 返回这一天 UTC 0 点的 unix 时间戳
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: u64 = parts.next()?.parse().ok()?;
+    let month: u64 = parts.next()?.parse().ok()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day)
+    {
+        return None;
     }
+
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    Some((era * 146097 + doe - 719468) * 86400)
+}
+
+// 压缩包解压后的目录名, 去掉 `.tar.gz` 这类复合后缀
+pub fn archive_stem(file: &Path) -> PathBuf {
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+    let lower_name = file_name.to_ascii_lowercase();
+
+    for compound_ext in [".tar.gz", ".tar.bz2", ".tar.xz", ".tar.zst"] {
+        if lower_name.ends_with(compound_ext) && lower_name.len() > compound_ext.len() {
+            return PathBuf::from(&file_name[..file_name.len() - compound_ext.len()]);
+        }
+    }
+
+    PathBuf::from(file.file_stem().unwrap_or_default())
+}
+
+// 压缩包在 target_dir 中对应的解压目录, 保持和 source_dir 中相同的相对位置
+pub fn archive_output_dir(source_dir: &Path, archive: &Archive, target_dir: &Path) -> PathBuf {
+    let new_root_file = change_path_root(source_dir, &archive.path, target_dir);
+    let mut new_root_file_comp = new_root_file.components();
+    new_root_file_comp.next_back();
+
+    new_root_file_comp.as_path().join(archive.stem())
 }
 
 pub fn change_path_root(old_root: &Path, path: &Path, new_root: &Path) -> PathBuf {
@@ -106,14 +160,14 @@ pub fn change_path_root(old_root: &Path, path: &Path, new_root: &Path) -> PathBu
         old_root_components.next();
     }
 
-    let output_path = new_root.join(components.as_path());
-
-    output_path
+    new_root.join(components.as_path())
 }
 
 #[cfg(test)]
 mod zip_test {
     use super::*;
+    use crate::zip::backend::{BackendRegistry, ExtractOptions};
+    use crate::zip::filter::EntryFilter;
 
     use assert_fs::prelude::*;
 
@@ -131,17 +185,21 @@ mod zip_test {
     #[test]
     fn test_collect_compressed() -> Result<(), Error> {
         let temp_project = assert_fs::TempDir::new().unwrap();
-        let _archive_file = temp_project
+        temp_project
             .child("test")
             .child("archive.tar.gz")
             .write_binary(&[]) // 创建一个空的 tar.gz 文件
             .unwrap();
 
-        let found_files = collect_compressed_files_in_dir(temp_project.path())?;
+        let found_files = collect_compressed_files_in_dir(
+            temp_project.path(),
+            DetectPolicy::default(),
+            &ScanPolicy::default(),
+        )?;
         assert!(!found_files.is_empty());
 
-        found_files.into_iter().for_each(|file| {
-            let ext = file.extension().unwrap_or_default();
+        found_files.into_iter().for_each(|archive| {
+            let ext = archive.path.extension().unwrap_or_default();
             assert_eq!(ext, "gz");
         });
 
@@ -154,30 +212,41 @@ mod zip_test {
         source_dir: &Path,
         target_dir: &Path,
         default_password: Option<String>,
-    ) -> Result<(), Error> {
-        let compressed_files = collect_compressed_files_in_dir(source_dir)?;
+    ) -> Result<Vec<PathBuf>, Error> {
+        let compressed_files = collect_compressed_files_in_dir(
+            source_dir,
+            DetectPolicy::default(),
+            &ScanPolicy::default(),
+        )?;
+        let registry = BackendRegistry::default();
+        let opts = ExtractOptions {
+            password: default_password,
+            ..Default::default()
+        };
+        let mut produced = Vec::new();
 
         for compressed_file in compressed_files {
             // generate new dir
-            let file_base_name = compressed_file.file_stem().unwrap();
-
-            let new_root_file = change_path_root(source_dir, &compressed_file, target_dir);
-            let mut new_root_file_comp = new_root_file.components();
-            new_root_file_comp.next_back();
-
-            let output_dir = new_root_file_comp.as_path().join(file_base_name);
-            println!("file: {:?} output_dir: {:?}", compressed_file, output_dir);
+            let output_dir = archive_output_dir(source_dir, &compressed_file, target_dir);
+            println!(
+                "file: {:?} output_dir: {:?}",
+                compressed_file.path, output_dir
+            );
             std::fs::create_dir_all(&output_dir)?;
 
             // unzip to output_dir
-            unzip_file(&compressed_file, &output_dir, default_password.clone()).await?;
+            produced.extend(
+                registry
+                    .extract(&compressed_file, &output_dir, &opts)?
+                    .files,
+            );
         }
 
-        Ok(())
+        Ok(produced)
     }
 
     fn create_zip_file(zip_file_path: &Path, files_to_compress: Vec<PathBuf>) -> ZipResult<()> {
-        let zip_file = File::create(&zip_file_path)?;
+        let zip_file = File::create(zip_file_path)?;
 
         let mut zip = ZipWriter::new(zip_file);
 
@@ -226,7 +295,7 @@ mod zip_test {
         let source_dir = temp_project.path().join("source");
         let output_dir = temp_project.path().join("output");
 
-        aw!(unzip_dir(&source_dir, &output_dir, None))?;
+        let produced = aw!(unzip_dir(&source_dir, &output_dir, None))?;
 
         let res_test_str_file = temp_project
             .path()
@@ -235,8 +304,149 @@ mod zip_test {
             .join("test_str.txt");
         println!("res_test_str_file: {}", res_test_str_file.display());
         assert!(res_test_str_file.exists());
+        assert_eq!(produced, vec![res_test_str_file]);
+
+        temp_project.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_tar_gz_dir() -> Result<(), Error> {
+        let temp_project = assert_fs::TempDir::new().unwrap();
+        temp_project.child("source").create_dir_all().unwrap();
+        temp_project.child("output").create_dir_all().unwrap();
+
+        let test_file = temp_project.child("test_str.txt");
+        test_file.write_str("hello").unwrap();
+
+        let tar_gz_path = temp_project.path().join("source").join("file.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&tar_gz_path)?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_path_with_name(test_file.path(), "inner/test_str.txt")?;
+        builder.into_inner()?.finish()?;
+
+        let source_dir = temp_project.path().join("source");
+        let output_dir = temp_project.path().join("output");
+
+        let produced = aw!(unzip_dir(&source_dir, &output_dir, None))?;
+
+        let res_test_str_file = output_dir.join("file").join("inner").join("test_str.txt");
+        assert!(res_test_str_file.exists());
+        assert_eq!(produced, vec![res_test_str_file]);
+
+        temp_project.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_archive_passthrough() -> Result<(), Error> {
+        let temp_project = assert_fs::TempDir::new().unwrap();
+        let files = temp_project.child("files");
+        files.child("a.txt").write_str("hello").unwrap();
+        files.child("b.log").write_str("log").unwrap();
+        // 后缀名像压缩包, 内容不是
+        files.child("core.gz").write_str("core dump").unwrap();
+
+        let tar_gz = files.child("logs.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(tar_gz.path())?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_path_with_name(files.child("b.log").path(), "b.log")?;
+        builder.into_inner()?.finish()?;
+
+        let zip_path = temp_project.path().join("file.zip");
+        let names = ["a.txt", "b.log", "core.gz", "logs.tar.gz"];
+        create_zip_file(
+            &zip_path,
+            names.map(|name| files.path().join(name)).to_vec(),
+        )
+        .unwrap();
+
+        let output_dir = temp_project.path().join("output");
+        std::fs::create_dir_all(&output_dir)?;
+        let opts = ExtractOptions {
+            filter: EntryFilter {
+                include: vec!["*.txt".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let report =
+            BackendRegistry::default().extract(&Archive::single(zip_path), &output_dir, &opts)?;
+
+        assert_eq!(
+            report.files,
+            vec![output_dir.join("a.txt"), output_dir.join("logs.tar.gz")]
+        );
+        assert_eq!(report.filtered, 2);
+        assert!(!output_dir.join("core.gz").exists());
+
+        temp_project.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_dir() -> Result<(), Error> {
+        let temp_project = assert_fs::TempDir::new().unwrap();
+        temp_project.child("source").create_dir_all().unwrap();
+
+        let test_file = temp_project.child("test_str.txt");
+        test_file.write_str("hello").unwrap();
+        let zip_path = temp_project.path().join("source").join("file.zip");
+        create_zip_file(&zip_path, vec![test_file.path().to_path_buf()]).unwrap();
+
+        let source_dir = temp_project.path().join("source");
+        let listings = aw!(crate::zip::list_dir(
+            source_dir,
+            DetectPolicy::default(),
+            ScanPolicy::default()
+        ))?;
+
+        assert_eq!(listings.len(), 1);
+        let entries = listings[0].entries.as_ref().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, PathBuf::from("test_str.txt"));
+        assert_eq!(entries[0].size, 5);
+        assert!(entries[0].compressed_size.is_some());
+        assert!(!entries[0].encrypted);
+        // 列出内容不会解压任何文件
+        assert_eq!(
+            std::fs::read_dir(temp_project.path().join("source"))?.count(),
+            1
+        );
 
         temp_project.close().unwrap();
         Ok(())
     }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-03-01"), Some(1709251200));
+        assert_eq!(format_unix_time(1709251200 + 3661), "2024-03-01 01:01:01");
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("2024-03"), None);
+        assert_eq!(parse_date("2024-02-29"), Some(1709164800));
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2024-02-31"), None);
+        assert_eq!(parse_date("2024-04-31"), None);
+        assert_eq!(parse_date("2000-02-29"), Some(951782400));
+        assert_eq!(parse_date("2100-02-29"), None);
+    }
+
+    #[test]
+    fn test_archive_stem() {
+        assert_eq!(
+            archive_stem(Path::new("a/foo.tar.gz")),
+            PathBuf::from("foo")
+        );
+        assert_eq!(archive_stem(Path::new("foo.TAR.ZST")), PathBuf::from("foo"));
+        assert_eq!(archive_stem(Path::new("foo.tgz")), PathBuf::from("foo"));
+        assert_eq!(archive_stem(Path::new("foo.zip")), PathBuf::from("foo"));
+    }
 }
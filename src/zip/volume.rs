@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::detect::format_from_extension;
+use super::utils::archive_stem;
+
+// 一个逻辑上的压缩包, 分卷压缩的所有分卷合并为同一项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Archive {
+    // 解压时打开的文件, 分卷压缩时是第一个分卷
+    pub path: PathBuf,
+    pub volumes: Vec<PathBuf>,
+    pub missing_volumes: Vec<PathBuf>,
+    stem: PathBuf,
+}
+
+impl Archive {
+    pub fn single(path: PathBuf) -> Self {
+        Self {
+            stem: archive_stem(&path),
+            volumes: vec![path.clone()],
+            missing_volumes: Vec::new(),
+            path,
+        }
+    }
+
+    pub fn is_multi_volume(&self) -> bool {
+        self.volumes.len() + self.missing_volumes.len() > 1
+    }
+
+    // 解压后的目录名
+    pub fn stem(&self) -> &Path {
+        &self.stem
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum VolumeScheme {
+    // x.part1.rar, x.part2.rar
+    RarPart,
+    // x.rar, x.r00, x.r01
+    RarOld,
+    // x.z01, x.z02, x.zip
+    ZipSplit,
+    // x.7z.001, x.7z.002
+    Numbered,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VolumeName {
+    scheme: VolumeScheme,
+    base: String,
+    number: u32,
+    width: usize,
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+// `part12` 这种前缀加数字的形式, 返回数字部分
+fn digits_after<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    let digits = &text[prefix.len()..];
+
+    (head.eq_ignore_ascii_case(prefix) && is_digits(digits)).then_some(digits)
+}
+
+fn parse_volume_name(file_name: &str) -> Option<VolumeName> {
+    let volume = |scheme, base: &str, digits: &str, offset: u32| {
+        Some(VolumeName {
+            scheme,
+            base: base.to_string(),
+            number: digits.parse::<u32>().ok()? + offset,
+            width: digits.len(),
+        })
+    };
+
+    let (base, ext) = file_name.rsplit_once('.')?;
+
+    if ext.eq_ignore_ascii_case("rar") {
+        let part = base
+            .rsplit_once('.')
+            .and_then(|(name, part)| Some((name, digits_after(part, "part")?)));
+
+        return match part {
+            Some((name, digits)) => volume(VolumeScheme::RarPart, name, digits, 0),
+            None => volume(VolumeScheme::RarOld, base, "0", 0),
+        };
+    }
+
+    if ext.eq_ignore_ascii_case("zip") {
+        return volume(VolumeScheme::ZipSplit, base, "0", 0);
+    }
+
+    if let Some(digits) = digits_after(ext, "r") {
+        return volume(VolumeScheme::RarOld, base, digits, 1);
+    }
+
+    if let Some(digits) = digits_after(ext, "z") {
+        return volume(VolumeScheme::ZipSplit, base, digits, 0);
+    }
+
+    if ext.len() >= 3 && is_digits(ext) && format_from_extension(Path::new(base)).is_some() {
+        return volume(VolumeScheme::Numbered, base, ext, 0);
+    }
+
+    None
+}
+
+// 后面的分卷 (x.z01, x.7z.002) 按后缀名识别不出来, 需要按分卷的命名规则收集
+pub fn is_volume_file(file: &Path) -> bool {
+    file.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(parse_volume_name)
+        .is_some_and(|volume| volume.number != 0)
+}
+
+fn volume_file_name(scheme: VolumeScheme, base: &str, number: u32, width: usize) -> String {
+    match scheme {
+        VolumeScheme::RarPart => format!("{}.part{:0width$}.rar", base, number),
+        VolumeScheme::RarOld if number == 0 => format!("{}.rar", base),
+        VolumeScheme::RarOld => format!("{}.r{:0width$}", base, number - 1),
+        VolumeScheme::ZipSplit if number == 0 => format!("{}.zip", base),
+        VolumeScheme::ZipSplit => format!("{}.z{:0width$}", base, number),
+        VolumeScheme::Numbered => format!("{}.{:0width$}", base, number),
+    }
+}
+
+struct VolumeGroup {
+    dir: PathBuf,
+    scheme: VolumeScheme,
+    base: String,
+    width: usize,
+    members: Vec<(u32, PathBuf)>,
+}
+
+impl VolumeGroup {
+    fn into_archive(mut self) -> Archive {
+        let is_lone_first = self.members.len() == 1
+            && self.members[0].0 == 0
+            && matches!(self.scheme, VolumeScheme::RarOld | VolumeScheme::ZipSplit);
+        if is_lone_first {
+            return Archive::single(self.members.remove(0).1);
+        }
+
+        self.members.sort_by_key(|(number, _)| *number);
+        let max_number = self.members.last().map(|(number, _)| *number).unwrap_or(0);
+
+        // zip 分卷的 .zip 是最后一卷, 但是解压时要打开它
+        let (expected, first_number): (Vec<u32>, u32) = match self.scheme {
+            VolumeScheme::RarPart | VolumeScheme::Numbered => ((1..=max_number).collect(), 1),
+            VolumeScheme::RarOld => ((0..=max_number).collect(), 0),
+            VolumeScheme::ZipSplit => ((1..=max_number).chain([0]).collect(), 0),
+        };
+
+        let volume_path = |number: u32| {
+            self.dir.join(volume_file_name(
+                self.scheme,
+                &self.base,
+                number,
+                self.width,
+            ))
+        };
+
+        // 没有收集到的分卷可能还在磁盘上, 例如按文件头识别时 x.zip 开头没有签名, 不会被收集
+        let mut volumes = Vec::new();
+        let mut missing_volumes = Vec::new();
+        for number in expected {
+            match self.members.iter().find(|(member, _)| *member == number) {
+                Some((_, path)) => volumes.push(path.clone()),
+                None if volume_path(number).exists() => volumes.push(volume_path(number)),
+                None => missing_volumes.push(volume_path(number)),
+            }
+        }
+
+        let stem = match self.scheme {
+            VolumeScheme::Numbered => archive_stem(Path::new(&self.base)),
+            _ => PathBuf::from(&self.base),
+        };
+
+        Archive {
+            path: volume_path(first_number),
+            volumes,
+            missing_volumes,
+            stem,
+        }
+    }
+}
+
+// 把同一组分卷合并成一个 Archive, 其余文件各自成为一个 Archive, 保持输入的顺序
+pub fn group_volumes(files: Vec<PathBuf>) -> Vec<Archive> {
+    enum Item {
+        Single(PathBuf),
+        Group(usize),
+    }
+
+    let mut items = Vec::new();
+    let mut groups: Vec<VolumeGroup> = Vec::new();
+    let mut group_index: HashMap<(PathBuf, String, VolumeScheme), usize> = HashMap::new();
+
+    for file in files {
+        let volume = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_volume_name);
+
+        let Some(volume) = volume else {
+            items.push(Item::Single(file));
+            continue;
+        };
+
+        let dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+        let key = (dir.clone(), volume.base.to_lowercase(), volume.scheme);
+
+        let index = *group_index.entry(key).or_insert_with(|| {
+            groups.push(VolumeGroup {
+                dir,
+                scheme: volume.scheme,
+                base: volume.base.clone(),
+                width: volume.width,
+                members: Vec::new(),
+            });
+            items.push(Item::Group(groups.len() - 1));
+            groups.len() - 1
+        });
+
+        let group = &mut groups[index];
+        if volume.number != 0 {
+            group.width = volume.width;
+        }
+        group.members.push((volume.number, file));
+    }
+
+    let mut groups: Vec<Option<VolumeGroup>> = groups.into_iter().map(Some).collect();
+    items
+        .into_iter()
+        .map(|item| match item {
+            Item::Single(path) => Archive::single(path),
+            Item::Group(index) => groups[index].take().unwrap().into_archive(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod volume_test {
+    use super::*;
+    use crate::error::{ArchiveErrorKind, Error};
+
+    #[test]
+    fn test_group_volumes() {
+        let files = [
+            "d/a.part1.rar",
+            "d/a.part3.rar",
+            "d/b.z01",
+            "d/b.zip",
+            "d/c.7z.001",
+            "d/c.7z.002",
+            "d/plain.zip",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let archives = group_volumes(files);
+        assert_eq!(archives.len(), 4);
+
+        assert_eq!(archives[0].path, PathBuf::from("d/a.part1.rar"));
+        assert_eq!(archives[0].volumes.len(), 2);
+        assert_eq!(
+            archives[0].missing_volumes,
+            vec![PathBuf::from("d/a.part2.rar")]
+        );
+        assert_eq!(archives[0].stem(), Path::new("a"));
+
+        assert_eq!(archives[1].path, PathBuf::from("d/b.zip"));
+        assert_eq!(
+            archives[1].volumes,
+            vec![PathBuf::from("d/b.z01"), PathBuf::from("d/b.zip")]
+        );
+        assert!(archives[1].missing_volumes.is_empty());
+
+        assert_eq!(archives[2].path, PathBuf::from("d/c.7z.001"));
+        assert_eq!(archives[2].volumes.len(), 2);
+        assert_eq!(archives[2].stem(), Path::new("c"));
+
+        assert!(!archives[3].is_multi_volume());
+
+        let result = crate::zip::backend::BackendRegistry::default().extract(
+            &archives[0],
+            Path::new("d"),
+            &Default::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::ArchiveError((ArchiveErrorKind::MissingVolume, _, _)))
+        ));
+    }
+
+    #[test]
+    fn test_uncollected_volume_on_disk() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let first = temp_dir.path().join("x.z01");
+        let last = temp_dir.path().join("x.zip");
+        std::fs::write(&first, b"").unwrap();
+        std::fs::write(&last, b"").unwrap();
+
+        let archives = group_volumes(vec![first.clone()]);
+        assert_eq!(archives.len(), 1);
+        assert_eq!(archives[0].path, last);
+        assert_eq!(archives[0].volumes, vec![first, last.clone()]);
+        assert!(archives[0].missing_volumes.is_empty());
+
+        temp_dir.close().unwrap();
+    }
+}